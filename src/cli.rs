@@ -0,0 +1,197 @@
+use chrono::TimeDelta;
+use chrono_tz::Tz;
+use clap::{ArgGroup, Parser, Subcommand};
+use std::path::PathBuf;
+
+use crate::{
+    clock::{CameraOffset, parse_clock_offset},
+    geotag::{ExistingGpsPolicy, WriteMode},
+    journal::BackupMode,
+    timeline::SourcePriority,
+};
+
+const DEFAULT_JOURNAL_DIR: &str = ".timeline_geotagger";
+
+/// Geotag photos using Google Maps Timeline data.
+///
+/// Any value not given on the command line is asked for interactively,
+/// unless `--no-input` is set or stdin is not a terminal.
+#[derive(Parser, Debug)]
+#[command(version, about, args_conflicts_with_subcommands = true)]
+#[command(group(ArgGroup::new("reference").args(["reference_time", "reference_location"])))]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Location history to take positions from: Timeline.json (Android, iOS
+    /// or Takeout export), a legacy Records.json, a GPX/KML/KMZ track, an NMEA
+    /// log, a FIT activity, a CSV or GeoJSON file, or a directory of them such
+    /// as Semantic Location History (repeatable; all are merged)
+    #[arg(long, value_name = "PATH")]
+    pub timeline: Vec<PathBuf>,
+
+    /// Weight one --timeline source against the others, e.g. "ride.gpx=1";
+    /// each step up halves its fixes' estimated error (default 0, repeatable)
+    #[arg(long, value_name = "PATH=PRIORITY", allow_hyphen_values = true)]
+    pub timeline_priority: Vec<SourcePriority>,
+
+    /// CSV column holding each fix's time (default: time, timestamp, date,
+    /// datetime or tst)
+    #[arg(long, value_name = "COLUMN")]
+    pub csv_time: Option<String>,
+
+    /// CSV column holding the latitude (default: lat or latitude)
+    #[arg(long, value_name = "COLUMN")]
+    pub csv_lat: Option<String>,
+
+    /// CSV column holding the longitude (default: lng, lon, long or longitude)
+    #[arg(long, value_name = "COLUMN")]
+    pub csv_lng: Option<String>,
+
+    /// CSV column holding the altitude in meters (default: alt, altitude,
+    /// elevation or ele, if present)
+    #[arg(long, value_name = "COLUMN")]
+    pub csv_altitude: Option<String>,
+
+    /// CSV column holding the accuracy in meters (default: accuracy, acc or
+    /// hdop_meters, if present)
+    #[arg(long, value_name = "COLUMN")]
+    pub csv_accuracy: Option<String>,
+
+    /// chrono format of the CSV time column, e.g. "%d/%m/%Y %H:%M:%S" or "%s"
+    /// for Unix seconds (default: RFC 3339, "YYYY-MM-DD HH:MM:SS" or Unix
+    /// seconds/milliseconds); times without an offset are UTC
+    #[arg(long, value_name = "FORMAT")]
+    pub csv_time_format: Option<String>,
+
+    /// Ignore fixes whose recorded accuracy is worse than METERS, e.g. 200 to
+    /// drop cell tower fixes (default: keep every fix)
+    #[arg(long, value_name = "METERS")]
+    pub max_accuracy: Option<f64>,
+
+    /// Only load positions within DAYS days of the photos' dates, to save
+    /// memory with large location histories (default: load everything)
+    #[arg(long, value_name = "DAYS")]
+    pub load_window: Option<u32>,
+
+    /// Photo directories or individual photos to geotag (repeatable)
+    #[arg(long, value_name = "PATH")]
+    pub photos: Vec<PathBuf>,
+
+    /// Search photo directories recursively
+    #[arg(short, long)]
+    pub recursive: bool,
+
+    /// Only geotag photos whose path relative to the photo directory matches
+    /// this glob (repeatable)
+    #[arg(long, value_name = "GLOB")]
+    pub include: Vec<String>,
+
+    /// Skip photos and directories whose path relative to the photo directory
+    /// matches this glob (repeatable)
+    #[arg(long, value_name = "GLOB")]
+    pub exclude: Vec<String>,
+
+    /// Follow symbolic links while searching directories
+    #[arg(long)]
+    pub follow_symlinks: bool,
+
+    /// Maximum number of directory levels to descend when searching recursively
+    #[arg(long, value_name = "N", requires = "recursive")]
+    pub max_depth: Option<usize>,
+
+    /// Timezone the photos were taken in (e.g. Europe/London)
+    #[arg(long, value_name = "TZ")]
+    pub timezone: Option<Tz>,
+
+    /// Work out each photo's UTC offset from the timezone offsets recorded in
+    /// the timeline; `--timezone` is used where the timeline is ambiguous
+    #[arg(long)]
+    pub auto_timezone: bool,
+
+    /// Correction added to every camera clock, e.g. +00:03:27 for a camera
+    /// running 3 minutes 27 seconds slow
+    #[arg(long, value_name = "[+-]HH:MM:SS", allow_hyphen_values = true, value_parser = parse_clock_offset)]
+    pub clock_offset: Option<TimeDelta>,
+
+    /// Clock correction for one camera, matched against the EXIF Model or
+    /// "Make Model", e.g. "Canon EOS R6=+00:03:27" (repeatable)
+    #[arg(long, value_name = "CAMERA=OFFSET", allow_hyphen_values = true)]
+    pub camera_offset: Vec<CameraOffset>,
+
+    /// Photo used to calibrate the clock offset of the camera that took it;
+    /// needs --reference-time or --reference-location
+    #[arg(long, value_name = "PHOTO", requires = "reference")]
+    pub reference_photo: Option<PathBuf>,
+
+    /// Time shown on the GPS device or phone screen in the reference photo,
+    /// as YYYY-MM-DD HH:MM:SS or HH:MM:SS
+    #[arg(long, value_name = "TIME", requires = "reference_photo")]
+    pub reference_time: Option<String>,
+
+    /// Where the reference photo was taken, as LAT,LNG; the offset is the
+    /// time the timeline was closest to it
+    #[arg(
+        long,
+        value_name = "LAT,LNG",
+        requires = "reference_photo",
+        allow_hyphen_values = true
+    )]
+    pub reference_location: Option<String>,
+
+    /// Where to write the GPS position
+    #[arg(long, value_enum, value_name = "MODE", default_value_t = WriteMode::Exif)]
+    pub write: WriteMode,
+
+    /// Maximum seconds between the fixes before and after a photo; photos
+    /// with a larger gap are reported as having no reliable location
+    #[arg(long, value_name = "SECONDS", value_parser = clap::value_parser!(i64).range(0..))]
+    pub max_gap: Option<i64>,
+
+    /// Maximum seconds between the fix before a photo and the photo itself
+    #[arg(long, value_name = "SECONDS", value_parser = clap::value_parser!(i64).range(0..))]
+    pub max_gap_before: Option<i64>,
+
+    /// Maximum seconds between a photo and the fix after it
+    #[arg(long, value_name = "SECONDS", value_parser = clap::value_parser!(i64).range(0..))]
+    pub max_gap_after: Option<i64>,
+
+    /// What to do with photos that already have GPS tags
+    #[arg(long, value_enum, value_name = "POLICY", default_value_t = ExistingGpsPolicy::Skip)]
+    pub existing_gps: ExistingGpsPolicy,
+
+    /// Distance in meters from the timeline position beyond which
+    /// `--existing-gps if-far` overwrites existing GPS tags
+    #[arg(long, value_name = "METERS", default_value_t = 500.0)]
+    pub far_distance: f64,
+
+    /// Save the original GPS tags or the whole file to a journal before
+    /// writing EXIF tags, so the run can be undone
+    #[arg(long, value_enum, value_name = "WHAT")]
+    pub backup: Option<BackupMode>,
+
+    /// Directory backup journals are stored in
+    #[arg(long, value_name = "DIR", default_value = DEFAULT_JOURNAL_DIR)]
+    pub journal_dir: PathBuf,
+
+    /// Report the location each photo would get without modifying any files
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Never prompt; fail if a required value was not given on the command line
+    #[arg(long)]
+    pub no_input: bool,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Restore the photos changed by a previous run from its backup journal
+    Undo {
+        /// Run to undo; lists the recorded runs when omitted
+        run: Option<String>,
+
+        /// Directory backup journals are stored in
+        #[arg(long, value_name = "DIR", default_value = DEFAULT_JOURNAL_DIR)]
+        journal_dir: PathBuf,
+    },
+}
@@ -0,0 +1,312 @@
+use chrono::{NaiveDateTime, NaiveTime, TimeDelta};
+use little_exif::{exif_tag::ExifTag, metadata::Metadata};
+use std::{error::Error, fmt, path::Path, str::FromStr};
+
+use crate::{
+    geotag::{self, GeotagOptions},
+    line::Point,
+    timeline::Timeline,
+};
+
+/// How far from the reference photo's time to look for the moment the
+/// timeline passed its known location
+const CALIBRATION_WINDOW_HOURS: i64 = 12;
+
+/// Parse a clock offset such as `+00:03:27` or `-1:00:00`. The offset is
+/// added to the camera's clock to get the true capture time.
+pub fn parse_clock_offset(offset: &str) -> Result<TimeDelta, String> {
+    let invalid = || format!("Invalid clock offset {}, expected [+-]HH:MM:SS", offset);
+
+    let trimmed = offset.trim();
+    let (sign, rest) = match trimmed.strip_prefix('-') {
+        Some(rest) => (-1, rest),
+        None => (1, trimmed.strip_prefix('+').unwrap_or(trimmed)),
+    };
+
+    let parts = rest
+        .split(':')
+        .map(|part| part.parse::<i64>().map_err(|_| invalid()))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let [hours, minutes, seconds] = parts[..] else {
+        return Err(invalid());
+    };
+    if hours < 0 || !(0..60).contains(&minutes) || !(0..60).contains(&seconds) {
+        return Err(invalid());
+    }
+
+    Ok(TimeDelta::seconds(
+        sign * (hours * 3600 + minutes * 60 + seconds),
+    ))
+}
+
+/// Format a clock offset the way `parse_clock_offset` reads it
+pub fn format_clock_offset(offset: TimeDelta) -> String {
+    let sign = if offset < TimeDelta::zero() { '-' } else { '+' };
+    let seconds = offset.num_seconds().abs();
+    format!(
+        "{}{:02}:{:02}:{:02}",
+        sign,
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+/// The camera a photo was taken with, from its `Make` and `Model` tags
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Camera {
+    pub make: Option<String>,
+    pub model: Option<String>,
+}
+
+impl Camera {
+    pub fn read(metadata: &Metadata) -> Self {
+        let make = match metadata.get_tag(&ExifTag::Make(String::new())).next() {
+            Some(ExifTag::Make(make)) => clean_tag(make),
+            _ => None,
+        };
+        let model = match metadata.get_tag(&ExifTag::Model(String::new())).next() {
+            Some(ExifTag::Model(model)) => clean_tag(model),
+            _ => None,
+        };
+
+        Self { make, model }
+    }
+
+    pub fn is_known(&self) -> bool {
+        self.make.is_some() || self.model.is_some()
+    }
+
+    /// Whether `name` refers to this camera, either by model alone or by
+    /// make and model, ignoring case
+    pub fn matches(&self, name: &str) -> bool {
+        let name = name.trim();
+        self.model
+            .as_deref()
+            .is_some_and(|model| model.eq_ignore_ascii_case(name))
+            || (self.is_known() && self.to_string().eq_ignore_ascii_case(name))
+    }
+}
+
+impl fmt::Display for Camera {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.make, &self.model) {
+            // Many cameras already repeat the make in the model name
+            (Some(make), Some(model))
+                if model
+                    .to_ascii_lowercase()
+                    .starts_with(&make.to_ascii_lowercase()) =>
+            {
+                write!(f, "{}", model)
+            }
+            (Some(make), Some(model)) => write!(f, "{} {}", make, model),
+            (Some(name), None) | (None, Some(name)) => write!(f, "{}", name),
+            (None, None) => write!(f, "unknown camera"),
+        }
+    }
+}
+
+fn clean_tag(value: &str) -> Option<String> {
+    let value = value.trim_matches(|c: char| c.is_whitespace() || c == '\0');
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+/// A clock offset for one camera, given as `CAMERA=OFFSET`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CameraOffset {
+    pub camera: String,
+    pub offset: TimeDelta,
+}
+
+impl FromStr for CameraOffset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (camera, offset) = s
+            .rsplit_once('=')
+            .ok_or_else(|| format!("Invalid camera offset {}, expected CAMERA=OFFSET", s))?;
+
+        Ok(Self {
+            camera: camera.trim().to_string(),
+            offset: parse_clock_offset(offset)?,
+        })
+    }
+}
+
+/// Clock corrections for a batch: per camera, with a default for the rest
+#[derive(Debug, Clone, Default)]
+pub struct ClockOffsets {
+    pub default: TimeDelta,
+    pub cameras: Vec<CameraOffset>,
+}
+
+impl ClockOffsets {
+    /// The offset for `camera`; the first matching camera offset wins
+    pub fn for_camera(&self, camera: &Camera) -> TimeDelta {
+        self.cameras
+            .iter()
+            .find(|camera_offset| camera.matches(&camera_offset.camera))
+            .map_or(self.default, |camera_offset| camera_offset.offset)
+    }
+
+    /// Use `offset` for `camera`, ahead of any offset given for it before
+    pub fn set(&mut self, camera: &Camera, offset: TimeDelta) {
+        if camera.is_known() {
+            self.cameras.insert(
+                0,
+                CameraOffset {
+                    camera: camera.to_string(),
+                    offset,
+                },
+            );
+        } else {
+            self.default = offset;
+        }
+    }
+}
+
+/// What the reference photo shows
+pub enum Reference {
+    /// The time on a GPS device or phone screen in the photo, as local time
+    /// or time of day
+    ShownTime(String),
+    /// Where the photo was taken, as `lat,lng`
+    Location(String),
+}
+
+/// Result of calibrating a camera's clock against a reference photo
+pub struct Calibration {
+    pub camera: Camera,
+    pub offset: TimeDelta,
+    /// For location references, how close the timeline came to the location
+    pub distance: Option<f64>,
+}
+
+/// Work out the clock offset of the camera that took `photo_path`
+pub fn calibrate(
+    timeline: &Timeline,
+    photo_path: &Path,
+    reference: &Reference,
+    options: &GeotagOptions,
+) -> Result<Calibration, Box<dyn Error>> {
+    let metadata = Metadata::new_from_path(photo_path)
+        .map_err(|e| format!("Failed to read metadata: {}", e))?;
+    let camera = Camera::read(&metadata);
+    let (local_time, exif_offset) = geotag::read_photo_time(&metadata)?;
+
+    match reference {
+        Reference::ShownTime(shown) => Ok(Calibration {
+            camera,
+            offset: parse_shown_time(shown, &local_time)? - local_time,
+            distance: None,
+        }),
+        Reference::Location(location) => {
+            let position = Point::parse_lat_lng(location)
+                .ok_or_else(|| format!("Invalid location {}, expected lat,lng", location))?;
+            let (photo_time, _) =
+                geotag::resolve_photo_time(timeline, &local_time, exif_offset, options)?;
+
+            let (point, distance) = timeline
+                .get_closest_point_to(
+                    position,
+                    &photo_time,
+                    TimeDelta::hours(CALIBRATION_WINDOW_HOURS),
+                )
+                .ok_or("The timeline has no positions near the reference photo's time")?;
+
+            Ok(Calibration {
+                camera,
+                offset: point.timestamp - photo_time,
+                distance: Some(distance),
+            })
+        }
+    }
+}
+
+/// Parse the time shown in a reference photo. A time of day alone is taken
+/// on whichever day puts it closest to the camera's time.
+fn parse_shown_time(shown: &str, camera_time: &NaiveDateTime) -> Result<NaiveDateTime, String> {
+    let shown = shown.trim();
+
+    for format in [
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y:%m:%d %H:%M:%S",
+    ] {
+        if let Ok(time) = NaiveDateTime::parse_from_str(shown, format) {
+            return Ok(time);
+        }
+    }
+
+    let time_of_day = NaiveTime::parse_from_str(shown, "%H:%M:%S")
+        .or_else(|_| NaiveTime::parse_from_str(shown, "%H:%M"))
+        .map_err(|_| format!("Invalid reference time {}", shown))?;
+
+    let date = camera_time.date();
+    [date.pred_opt(), Some(date), date.succ_opt()]
+        .into_iter()
+        .flatten()
+        .map(|date| date.and_time(time_of_day))
+        .min_by_key(|time| (*time - *camera_time).abs())
+        .ok_or_else(|| format!("Invalid reference time {}", shown))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_clock_offset() {
+        assert_eq!(parse_clock_offset("+00:03:27"), Ok(TimeDelta::seconds(207)));
+        assert_eq!(parse_clock_offset("-1:00:00"), Ok(TimeDelta::hours(-1)));
+        assert_eq!(parse_clock_offset("00:00:05"), Ok(TimeDelta::seconds(5)));
+        assert!(parse_clock_offset("00:03").is_err());
+        assert!(parse_clock_offset("+00:60:00").is_err());
+
+        assert_eq!(format_clock_offset(TimeDelta::seconds(207)), "+00:03:27");
+        assert_eq!(format_clock_offset(TimeDelta::seconds(-3725)), "-01:02:05");
+    }
+
+    #[test]
+    fn test_camera_offsets() {
+        let canon = Camera {
+            make: Some("Canon".to_string()),
+            model: Some("Canon EOS R6".to_string()),
+        };
+        let fuji = Camera {
+            make: Some("FUJIFILM".to_string()),
+            model: Some("X100V".to_string()),
+        };
+        assert_eq!(canon.to_string(), "Canon EOS R6");
+        assert_eq!(fuji.to_string(), "FUJIFILM X100V");
+
+        let mut offsets = ClockOffsets {
+            default: TimeDelta::seconds(10),
+            cameras: vec!["canon eos r6=+00:03:27".parse().unwrap()],
+        };
+        assert_eq!(offsets.for_camera(&canon), TimeDelta::seconds(207));
+        assert_eq!(offsets.for_camera(&fuji), TimeDelta::seconds(10));
+        assert_eq!(
+            offsets.for_camera(&Camera::default()),
+            TimeDelta::seconds(10)
+        );
+
+        offsets.set(&fuji, TimeDelta::seconds(-60));
+        assert_eq!(offsets.for_camera(&fuji), TimeDelta::seconds(-60));
+        assert!(fuji.matches("x100v"));
+    }
+
+    #[test]
+    fn test_parse_shown_time() {
+        let camera_time =
+            NaiveDateTime::parse_from_str("2025-08-11 23:58:00", "%Y-%m-%d %H:%M:%S").unwrap();
+
+        let shown = parse_shown_time("2025-08-11 23:59:30", &camera_time).unwrap();
+        assert_eq!(shown - camera_time, TimeDelta::seconds(90));
+
+        // Just after midnight on the next day
+        let shown = parse_shown_time("00:01:27", &camera_time).unwrap();
+        assert_eq!(shown - camera_time, TimeDelta::seconds(207));
+    }
+}
@@ -0,0 +1,250 @@
+use chrono::{DateTime, NaiveDateTime, Utc};
+use std::error::Error;
+
+use crate::{line::PointSource, track::TrackPoint};
+
+/// Header names tried, in order, for columns that aren't given explicitly.
+/// They cover the CSV exports of OwnTracks and GPSLogger.
+const TIME_COLUMNS: [&str; 5] = ["time", "timestamp", "date", "datetime", "tst"];
+const LAT_COLUMNS: [&str; 2] = ["lat", "latitude"];
+const LNG_COLUMNS: [&str; 4] = ["lng", "lon", "long", "longitude"];
+const ALTITUDE_COLUMNS: [&str; 4] = ["alt", "altitude", "elevation", "ele"];
+const ACCURACY_COLUMNS: [&str; 3] = ["accuracy", "acc", "hdop_meters"];
+
+/// Which columns of a CSV file hold what, and how its times are written.
+/// Columns left unset are found by their usual header names.
+#[derive(Debug, Clone, Default)]
+pub struct CsvFormat {
+    pub time_column: Option<String>,
+    pub lat_column: Option<String>,
+    pub lng_column: Option<String>,
+    pub altitude_column: Option<String>,
+    pub accuracy_column: Option<String>,
+    /// chrono format string, e.g. `%d/%m/%Y %H:%M:%S` or `%s` for Unix
+    /// seconds; times without an offset are taken as UTC
+    pub time_format: Option<String>,
+}
+
+/// Index of the column named `name`, or else of the first of `defaults`
+/// present in `headers`
+fn find_column(
+    headers: &csv::StringRecord,
+    name: Option<&str>,
+    defaults: &[&str],
+) -> Result<Option<usize>, String> {
+    let position = |wanted: &str| {
+        headers
+            .iter()
+            .position(|header| header.trim().eq_ignore_ascii_case(wanted))
+    };
+
+    match name {
+        Some(name) => position(name)
+            .map(Some)
+            .ok_or_else(|| format!("CSV file has no column named \"{}\"", name)),
+        None => Ok(defaults.iter().find_map(|name| position(name))),
+    }
+}
+
+/// Parse a time with `format`, or else as RFC 3339, `YYYY-MM-DD HH:MM:SS` in
+/// UTC, or Unix seconds or milliseconds
+pub fn parse_time(value: &str, format: Option<&str>) -> Option<DateTime<Utc>> {
+    let value = value.trim();
+
+    if let Some(format) = format {
+        return DateTime::parse_from_str(value, format)
+            .map(|timestamp| timestamp.to_utc())
+            .or_else(|_| NaiveDateTime::parse_from_str(value, format).map(|time| time.and_utc()))
+            .ok();
+    }
+
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(value) {
+        return Some(timestamp.to_utc());
+    }
+    if let Ok(time) = NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S%.f") {
+        return Some(time.and_utc());
+    }
+
+    // f64 parsing also accepts "NaN" and "inf", which are no time at all
+    let seconds = value
+        .parse::<f64>()
+        .ok()
+        .filter(|seconds| seconds.is_finite())?;
+    // Anything after the year 5138 in seconds is milliseconds
+    let milliseconds = if seconds.abs() > 1e11 {
+        seconds
+    } else {
+        seconds * 1000.0
+    };
+    DateTime::from_timestamp_millis(milliseconds.round() as i64)
+}
+
+/// Parse a CSV file with a header row into track points. Rows whose time or
+/// position can't be read are skipped, but a file where no row can be read
+/// is an error, since the columns or time format are probably wrong.
+pub fn parse(bytes: &[u8], format: &CsvFormat) -> Result<Vec<TrackPoint>, Box<dyn Error>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(bytes);
+    let headers = reader.headers()?.clone();
+
+    let column = |name: &Option<String>, defaults: &[&str], label: &str| {
+        find_column(&headers, name.as_deref(), defaults)?
+            .ok_or_else(|| format!("CSV file has no {} column", label))
+    };
+    let time = column(&format.time_column, &TIME_COLUMNS, "time")?;
+    let lat = column(&format.lat_column, &LAT_COLUMNS, "latitude")?;
+    let lng = column(&format.lng_column, &LNG_COLUMNS, "longitude")?;
+    let altitude = find_column(
+        &headers,
+        format.altitude_column.as_deref(),
+        &ALTITUDE_COLUMNS,
+    )?;
+    let accuracy = find_column(
+        &headers,
+        format.accuracy_column.as_deref(),
+        &ACCURACY_COLUMNS,
+    )?;
+
+    let mut points = Vec::new();
+    let mut rows = 0;
+
+    for row in reader.records() {
+        let row = row?;
+        rows += 1;
+
+        // f64 parsing also accepts "NaN" and "inf", which are no number at all
+        let number = |index: Option<usize>| -> Option<f64> {
+            let number: f64 = row.get(index?)?.trim().parse().ok()?;
+            number.is_finite().then_some(number)
+        };
+        let Some(timestamp) = row
+            .get(time)
+            .and_then(|value| parse_time(value, format.time_format.as_deref()))
+        else {
+            continue;
+        };
+        let (Some(lat), Some(lng)) = (number(Some(lat)), number(Some(lng))) else {
+            continue;
+        };
+        if lat.abs() > 90.0 || lng.abs() > 180.0 {
+            continue;
+        }
+
+        points.push(TrackPoint {
+            lat,
+            lng,
+            altitude: number(altitude),
+            accuracy: number(accuracy),
+            speed: None,
+            timestamp,
+            source: PointSource::Csv,
+        });
+    }
+
+    if points.is_empty() && rows > 0 {
+        return Err(format!(
+            "None of the {} rows could be read; check the time format and columns",
+            rows
+        )
+        .into());
+    }
+
+    Ok(points)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timeline::{SourceOptions, Timeline};
+
+    #[test]
+    fn test_parse_time() {
+        let expected = "2025-08-11T15:20:00+00:00";
+        for value in [
+            "2025-08-11T16:20:00+01:00",
+            "2025-08-11 15:20:00",
+            "1754925600",
+            "1754925600000",
+        ] {
+            assert_eq!(parse_time(value, None).unwrap().to_rfc3339(), expected);
+        }
+
+        let time = parse_time("11/08/2025 15:20", Some("%d/%m/%Y %H:%M")).unwrap();
+        assert_eq!(time.to_rfc3339(), expected);
+        let time = parse_time("11/08/2025 16:20 +0100", Some("%d/%m/%Y %H:%M %z")).unwrap();
+        assert_eq!(time.to_rfc3339(), expected);
+        let time = parse_time("1754925600", Some("%s")).unwrap();
+        assert_eq!(time.to_rfc3339(), expected);
+        assert!(parse_time("2025-08-11 15:20:00", Some("%d/%m/%Y %H:%M")).is_none());
+
+        for value in ["NaN", "inf", "-infinity"] {
+            assert!(parse_time(value, None).is_none());
+        }
+    }
+
+    #[test]
+    fn test_parse_default_columns() {
+        let csv = "\
+tst,lat,lon,alt,acc,batt
+1754925600,54.7768,-1.5757,42,12,88
+not a time,54.7770,-1.5760,43,12,88
+1754925620,NaN,-1.5760,43,12,88
+1754925630,54.7770,inf,43,12,88
+1754925640,91.0,-1.5760,43,12,88
+1754925650,54.7770,-181.0,43,12,88
+1754925660,54.7772,-1.5763,NaN,5,87
+";
+        let points = parse(csv.as_bytes(), &CsvFormat::default()).unwrap();
+
+        assert_eq!(points.len(), 2);
+        assert_eq!(points[0].lat, 54.7768);
+        assert_eq!(points[0].lng, -1.5757);
+        assert_eq!(points[0].altitude, Some(42.0));
+        assert_eq!(points[0].accuracy, Some(12.0));
+        assert_eq!(
+            points[0].timestamp.to_rfc3339(),
+            "2025-08-11T15:20:00+00:00"
+        );
+        assert_eq!(points[1].altitude, None);
+        assert_eq!(points[1].source, PointSource::Csv);
+    }
+
+    #[test]
+    fn test_parse_mapped_columns() {
+        let format = CsvFormat {
+            time_column: Some("Recorded".to_string()),
+            lat_column: Some("Y".to_string()),
+            lng_column: Some("X".to_string()),
+            time_format: Some("%d.%m.%Y %H:%M:%S".to_string()),
+            ..Default::default()
+        };
+        let options = SourceOptions {
+            csv: format.clone(),
+            ..Default::default()
+        };
+        let data = Timeline::from_path_with("tests/track_example.csv", &options).unwrap();
+
+        let timestamp = DateTime::parse_from_rfc3339("2025-08-11T15:20:30Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let point = data.get_point_at(&timestamp).unwrap();
+        assert_eq!(point.source, PointSource::Csv);
+        assert!((point.lat - 54.777).abs() < 1e-6);
+        assert_eq!(point.altitude, Some(42.5));
+
+        // The columns aren't found without the mapping
+        assert!(Timeline::from_path("tests/track_example.csv").is_err());
+
+        let format = CsvFormat {
+            lat_column: Some("Latitude".to_string()),
+            ..format
+        };
+        let error = parse(b"Recorded,Y,X\n", &format).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "CSV file has no column named \"Latitude\""
+        );
+    }
+}
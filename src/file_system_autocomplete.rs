@@ -65,30 +65,28 @@ impl Autocomplete for FileSystemAutocomplete {
         let mut suggestions: Vec<String> = Vec::new();
 
         if let Ok(entries) = fs::read_dir(&base_dir) {
-            for entry_result in entries {
-                if let Ok(entry) = entry_result {
-                    let file_name_os = entry.file_name();
-                    let file_name = file_name_os.to_string_lossy();
-                    if file_name.starts_with(&partial_name) {
-                        let full_path = base_dir.join(file_name.as_ref());
-                        if !self.files && full_path.is_file() {
-                            continue; // Skip files if only directories are requested
-                        }
-                        let mut display = full_path.to_string_lossy().to_string();
-                        if full_path.is_dir() {
-                            let sep = std::path::MAIN_SEPARATOR;
-                            if !display.ends_with(sep) {
-                                display.push(sep);
-                            }
+            for entry in entries.flatten() {
+                let file_name_os = entry.file_name();
+                let file_name = file_name_os.to_string_lossy();
+                if file_name.starts_with(&partial_name) {
+                    let full_path = base_dir.join(file_name.as_ref());
+                    if !self.files && full_path.is_file() {
+                        continue; // Skip files if only directories are requested
+                    }
+                    let mut display = full_path.to_string_lossy().to_string();
+                    if full_path.is_dir() {
+                        let sep = std::path::MAIN_SEPARATOR;
+                        if !display.ends_with(sep) {
+                            display.push(sep);
                         }
-                        suggestions.push(display);
                     }
+                    suggestions.push(display);
                 }
             }
         }
 
         // Sort suggestions: case-insensitive, directories and files mixed but stable
-        suggestions.sort_by(|a, b| a.to_lowercase().cmp(&b.to_lowercase()));
+        suggestions.sort_by_key(|a| a.to_lowercase());
         Ok(suggestions)
     }
 }
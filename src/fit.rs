@@ -0,0 +1,295 @@
+use chrono::DateTime;
+use std::error::Error;
+
+use crate::{line::PointSource, track::TrackPoint};
+
+/// FIT timestamps count seconds from 1989-12-31T00:00:00Z
+const FIT_EPOCH_UNIX_SECONDS: i64 = 631_065_600;
+
+/// Global message number of `record` messages
+const RECORD_MESSAGE: u16 = 20;
+
+/// `record` field numbers
+const TIMESTAMP_FIELD: u8 = 253;
+const POSITION_LAT_FIELD: u8 = 0;
+const POSITION_LONG_FIELD: u8 = 1;
+const ALTITUDE_FIELD: u8 = 2;
+const SPEED_FIELD: u8 = 6;
+const ENHANCED_SPEED_FIELD: u8 = 73;
+const ENHANCED_ALTITUDE_FIELD: u8 = 78;
+
+/// Layout of the data messages of one local message type
+struct Definition {
+    global_message: u16,
+    big_endian: bool,
+    /// Field number and size in bytes of each field, in order
+    fields: Vec<(u8, usize)>,
+    /// Total size of the developer fields that follow, which are skipped
+    developer_size: usize,
+}
+
+/// A `record` message being decoded
+#[derive(Default)]
+struct Record {
+    timestamp: Option<u32>,
+    lat: Option<i32>,
+    lng: Option<i32>,
+    altitude: Option<f64>,
+    enhanced_altitude: Option<f64>,
+    speed: Option<f64>,
+    enhanced_speed: Option<f64>,
+}
+
+impl Record {
+    fn into_track_point(self) -> Option<TrackPoint> {
+        let timestamp =
+            DateTime::from_timestamp(FIT_EPOCH_UNIX_SECONDS + i64::from(self.timestamp?), 0)?;
+
+        Some(TrackPoint {
+            lat: from_semicircles(self.lat?),
+            lng: from_semicircles(self.lng?),
+            altitude: self.enhanced_altitude.or(self.altitude),
+            accuracy: None,
+            speed: self.enhanced_speed.or(self.speed),
+            timestamp,
+            source: PointSource::Fit,
+        })
+    }
+}
+
+fn from_semicircles(semicircles: i32) -> f64 {
+    f64::from(semicircles) * (180.0 / 2f64.powi(31))
+}
+
+/// Reads through the bytes of a FIT file
+struct Cursor<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], Box<dyn Error>> {
+        let bytes = self
+            .bytes
+            .get(self.position..self.position + length)
+            .ok_or("FIT file is truncated")?;
+        self.position += length;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8, Box<dyn Error>> {
+        Ok(self.take(1)?[0])
+    }
+}
+
+/// Unsigned integer of up to 8 bytes
+fn read_uint(bytes: &[u8], big_endian: bool) -> u64 {
+    let fold = |value: u64, byte: &u8| (value << 8) | u64::from(*byte);
+    if big_endian {
+        bytes.iter().fold(0, fold)
+    } else {
+        bytes.iter().rev().fold(0, fold)
+    }
+}
+
+/// Read a definition message: the layout of the following data messages with
+/// its local message type
+fn read_definition(
+    cursor: &mut Cursor,
+    has_developer_fields: bool,
+) -> Result<Definition, Box<dyn Error>> {
+    let _reserved = cursor.byte()?;
+    let big_endian = cursor.byte()? == 1;
+    let global_message = read_uint(cursor.take(2)?, big_endian) as u16;
+
+    let field_count = cursor.byte()?;
+    let mut fields = Vec::with_capacity(usize::from(field_count));
+    for _ in 0..field_count {
+        let field = cursor.take(3)?;
+        fields.push((field[0], usize::from(field[1])));
+    }
+
+    let mut developer_size = 0;
+    if has_developer_fields {
+        for _ in 0..cursor.byte()? {
+            developer_size += usize::from(cursor.take(3)?[1]);
+        }
+    }
+
+    Ok(Definition {
+        global_message,
+        big_endian,
+        fields,
+        developer_size,
+    })
+}
+
+/// Timestamp of a compressed timestamp header: the 5-bit `offset` applied to
+/// the last full timestamp, allowing for it to have rolled over
+fn compressed_timestamp(last_timestamp: u32, offset: u32) -> u32 {
+    let base = last_timestamp & !0x1F;
+    if offset >= last_timestamp & 0x1F {
+        base + offset
+    } else {
+        base + offset + 0x20
+    }
+}
+
+/// Parse the positions of the `record` messages of a FIT activity file, as
+/// written by Garmin and Wahoo devices. Records without a position, such as
+/// those from indoor rides, are skipped.
+pub fn parse(bytes: &[u8]) -> Result<Vec<TrackPoint>, Box<dyn Error>> {
+    let mut points = Vec::new();
+    let mut cursor = Cursor { bytes, position: 0 };
+
+    // A file may hold several FIT files back to back
+    while cursor.position < bytes.len() {
+        let header_size = usize::from(cursor.byte()?);
+        let header = cursor.take(header_size.saturating_sub(1))?;
+        if header_size < 12 || &header[7..11] != b".FIT" {
+            return Err("Not a FIT file".into());
+        }
+        let data_size = read_uint(&header[3..7], false) as usize;
+        let end = cursor.position + data_size;
+
+        let mut definitions: [Option<Definition>; 16] = Default::default();
+        let mut last_timestamp: Option<u32> = None;
+
+        while cursor.position < end {
+            let record_header = cursor.byte()?;
+
+            let (local_message, compressed_offset) = if record_header & 0x80 != 0 {
+                (
+                    (record_header >> 5) & 0x03,
+                    Some(u32::from(record_header & 0x1F)),
+                )
+            } else if record_header & 0x40 != 0 {
+                let local_message = usize::from(record_header & 0x0F);
+                definitions[local_message] =
+                    Some(read_definition(&mut cursor, record_header & 0x20 != 0)?);
+                continue;
+            } else {
+                (record_header & 0x0F, None)
+            };
+
+            let definition = definitions[usize::from(local_message)]
+                .as_ref()
+                .ok_or("FIT data message without a definition")?;
+
+            let mut record = Record {
+                timestamp: last_timestamp
+                    .zip(compressed_offset)
+                    .map(|(last, offset)| compressed_timestamp(last, offset)),
+                ..Default::default()
+            };
+
+            for &(field, size) in &definition.fields {
+                let value = read_uint(cursor.take(size)?, definition.big_endian);
+                match (field, size) {
+                    (TIMESTAMP_FIELD, 4) if value != 0xFFFF_FFFF => {
+                        record.timestamp = Some(value as u32);
+                    }
+                    (POSITION_LAT_FIELD, 4) if value != 0x7FFF_FFFF => {
+                        record.lat = Some(value as u32 as i32);
+                    }
+                    (POSITION_LONG_FIELD, 4) if value != 0x7FFF_FFFF => {
+                        record.lng = Some(value as u32 as i32);
+                    }
+                    (ALTITUDE_FIELD, 2) if value != 0xFFFF => {
+                        record.altitude = Some(value as f64 / 5.0 - 500.0);
+                    }
+                    (ENHANCED_ALTITUDE_FIELD, 4) if value != 0xFFFF_FFFF => {
+                        record.enhanced_altitude = Some(value as f64 / 5.0 - 500.0);
+                    }
+                    (SPEED_FIELD, 2) if value != 0xFFFF => {
+                        record.speed = Some(value as f64 / 1000.0);
+                    }
+                    (ENHANCED_SPEED_FIELD, 4) if value != 0xFFFF_FFFF => {
+                        record.enhanced_speed = Some(value as f64 / 1000.0);
+                    }
+                    _ => {}
+                }
+            }
+            cursor.take(definition.developer_size)?;
+
+            // Compressed timestamps follow on from the last timestamp of any
+            // message
+            if record.timestamp.is_some() {
+                last_timestamp = record.timestamp;
+            }
+            if definition.global_message == RECORD_MESSAGE {
+                points.extend(record.into_track_point());
+            }
+        }
+
+        // File CRC
+        cursor.take(2)?;
+    }
+
+    Ok(points)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timeline::Timeline;
+    use chrono::Utc;
+
+    #[test]
+    fn test_compressed_timestamp() {
+        assert_eq!(compressed_timestamp(0x40, 0x05), 0x45);
+        assert_eq!(compressed_timestamp(0x5E, 0x1F), 0x5F);
+        // The offset wrapped past 0x1F
+        assert_eq!(compressed_timestamp(0x5E, 0x02), 0x62);
+    }
+
+    #[test]
+    fn test_parse() {
+        let points = parse(&std::fs::read("tests/track_example.fit").unwrap()).unwrap();
+
+        assert!((points[0].lat - 54.7768).abs() < 1e-6);
+        assert!((points[0].lng - -1.5757).abs() < 1e-6);
+        assert_eq!(points[0].altitude, Some(42.0));
+        assert_eq!(points[0].speed, Some(4.25));
+        assert_eq!(
+            points[0].timestamp.to_rfc3339(),
+            "2025-08-11T15:20:00+00:00"
+        );
+
+        // The record without a position is skipped; the next has a
+        // compressed timestamp header and a plain altitude
+        assert_eq!(points[1].altitude, Some(44.0));
+        assert_eq!(points[1].speed, None);
+        assert_eq!(
+            points[1].timestamp.to_rfc3339(),
+            "2025-08-11T15:20:10+00:00"
+        );
+
+        // Big-endian definition
+        assert!((points[2].lat - 54.7776).abs() < 1e-6);
+        assert_eq!(
+            points[2].timestamp.to_rfc3339(),
+            "2025-08-11T15:21:00+00:00"
+        );
+
+        assert_eq!(points.len(), 3);
+        assert!(points.iter().all(|point| point.source == PointSource::Fit));
+    }
+
+    #[test]
+    fn test_fit_as_timeline() {
+        let data = Timeline::from_path("tests/track_example.fit").unwrap();
+
+        let timestamp = DateTime::parse_from_rfc3339("2025-08-11T15:20:05Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let point = data.get_point_at(&timestamp).unwrap();
+        assert_eq!(point.source, PointSource::Fit);
+        assert_eq!(point.altitude, Some(43.0));
+    }
+
+    #[test]
+    fn test_parse_rejects_other_files() {
+        assert!(parse(b"{\"semanticSegments\": []}").is_err());
+    }
+}
@@ -0,0 +1,180 @@
+use chrono::{DateTime, Utc};
+use serde_json::{Map, Value};
+use std::error::Error;
+
+use crate::{csv_track::parse_time, line::PointSource, track::TrackPoint};
+
+/// Properties tried, in order, for the time of a `Point` feature
+const TIME_PROPERTIES: [&str; 5] = ["time", "timestamp", "datetime", "date", "tst"];
+
+/// Properties tried for the accuracy in meters of a `Point` feature
+const ACCURACY_PROPERTIES: [&str; 2] = ["accuracy", "acc"];
+
+/// A time property: an RFC 3339 string or Unix seconds or milliseconds
+fn value_time(value: &Value) -> Option<DateTime<Utc>> {
+    match value {
+        Value::String(text) => parse_time(text, None),
+        Value::Number(number) => parse_time(&number.to_string(), None),
+        _ => None,
+    }
+}
+
+fn first_property<'a>(properties: &'a Map<String, Value>, names: &[&str]) -> Option<&'a Value> {
+    names.iter().find_map(|name| properties.get(*name))
+}
+
+/// Parse a GeoJSON `[lng, lat, alt?]` position
+fn parse_position(value: &Value) -> Option<(f64, f64, Option<f64>)> {
+    let position = value.as_array()?;
+    let lng = position.first()?.as_f64()?;
+    let lat = position.get(1)?.as_f64()?;
+    Some((lat, lng, position.get(2).and_then(Value::as_f64)))
+}
+
+fn track_point(
+    (lat, lng, altitude): (f64, f64, Option<f64>),
+    accuracy: Option<f64>,
+    timestamp: DateTime<Utc>,
+) -> TrackPoint {
+    TrackPoint {
+        lat,
+        lng,
+        altitude,
+        accuracy,
+        speed: None,
+        timestamp,
+        source: PointSource::GeoJson,
+    }
+}
+
+/// Pair the positions of a line with its `coordTimes`
+fn line_points(coordinates: &Value, times: &Value) -> Vec<TrackPoint> {
+    let (Some(coordinates), Some(times)) = (coordinates.as_array(), times.as_array()) else {
+        return Vec::new();
+    };
+
+    coordinates
+        .iter()
+        .zip(times)
+        .filter_map(|(position, time)| {
+            Some(track_point(
+                parse_position(position)?,
+                None,
+                value_time(time)?,
+            ))
+        })
+        .collect()
+}
+
+fn feature_points(feature: &Value) -> Vec<TrackPoint> {
+    let empty = Map::new();
+    let properties = feature
+        .get("properties")
+        .and_then(Value::as_object)
+        .unwrap_or(&empty);
+    let Some(geometry) = feature.get("geometry") else {
+        return Vec::new();
+    };
+    let coordinates = &geometry["coordinates"];
+    let coord_times = properties.get("coordTimes").unwrap_or(&Value::Null);
+
+    match geometry["type"].as_str() {
+        Some("Point") => {
+            let time = first_property(properties, &TIME_PROPERTIES).and_then(value_time);
+            let accuracy = first_property(properties, &ACCURACY_PROPERTIES).and_then(Value::as_f64);
+            match (parse_position(coordinates), time) {
+                (Some(position), Some(time)) => vec![track_point(position, accuracy, time)],
+                _ => Vec::new(),
+            }
+        }
+        Some("LineString") => line_points(coordinates, coord_times),
+        // One array of times per line
+        Some("MultiLineString") => coordinates
+            .as_array()
+            .into_iter()
+            .flatten()
+            .zip(coord_times.as_array().into_iter().flatten())
+            .flat_map(|(line, times)| line_points(line, times))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Parse the timestamped positions of a GeoJSON `FeatureCollection` or
+/// `Feature`: `Point` features with a time property, and `LineString` or
+/// `MultiLineString` features with a `coordTimes` property, as written by
+/// togeojson and GPS loggers. Features without times are skipped.
+pub fn parse(bytes: &[u8]) -> Result<Vec<TrackPoint>, Box<dyn Error>> {
+    let document: Value = serde_json::from_slice(bytes)?;
+
+    match document["type"].as_str() {
+        Some("FeatureCollection") => Ok(document["features"]
+            .as_array()
+            .ok_or("GeoJSON FeatureCollection has no features")?
+            .iter()
+            .flat_map(feature_points)
+            .collect()),
+        Some("Feature") => Ok(feature_points(&document)),
+        _ => Err("Not a GeoJSON FeatureCollection or Feature".into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timeline::Timeline;
+
+    #[test]
+    fn test_parse() {
+        let points = parse(&std::fs::read("tests/track_example.geojson").unwrap()).unwrap();
+
+        // Point feature with an ISO time and an accuracy
+        assert_eq!((points[0].lat, points[0].lng), (54.7768, -1.5757));
+        assert_eq!(points[0].altitude, Some(42.0));
+        assert_eq!(points[0].accuracy, Some(8.0));
+        assert_eq!(
+            points[0].timestamp.to_rfc3339(),
+            "2025-08-11T15:20:00+00:00"
+        );
+
+        // Point feature with Unix seconds
+        assert_eq!(
+            points[1].timestamp.to_rfc3339(),
+            "2025-08-11T15:21:00+00:00"
+        );
+
+        // LineString with coordTimes; the feature without times is skipped
+        assert_eq!((points[2].lat, points[2].lng), (54.78, -1.58));
+        assert_eq!(points[2].altitude, None);
+        assert_eq!(
+            points[3].timestamp.to_rfc3339(),
+            "2025-08-11T15:31:00+00:00"
+        );
+
+        // MultiLineString
+        assert_eq!(
+            points[5].timestamp.to_rfc3339(),
+            "2025-08-11T15:41:00+00:00"
+        );
+
+        assert_eq!(points.len(), 6);
+        assert!(
+            points
+                .iter()
+                .all(|point| point.source == PointSource::GeoJson)
+        );
+    }
+
+    #[test]
+    fn test_geojson_as_timeline() {
+        let data = Timeline::from_path("tests/track_example.geojson").unwrap();
+
+        let timestamp = DateTime::parse_from_rfc3339("2025-08-11T15:30:30Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let line = data.get_line_at(&timestamp).unwrap();
+        assert_eq!(line.start.source, PointSource::GeoJson);
+        assert_eq!(line.start.relative_seconds, -30);
+        assert_eq!(line.end.relative_seconds, 30);
+    }
+}
@@ -1,10 +1,22 @@
-use std::{fs, path::Path};
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
 
-use chrono::{NaiveDateTime, Timelike};
+use chrono::{DateTime, FixedOffset, NaiveDateTime, TimeDelta, Timelike, Utc};
 use chrono_tz::Tz;
+use clap::ValueEnum;
 use little_exif::{exif_tag::ExifTag, metadata::Metadata};
 
-use crate::timeline::Timeline;
+use crate::{
+    clock::{Camera, ClockOffsets, format_clock_offset},
+    gps_tags::GpsTags,
+    journal::Journal,
+    line::{MaxGap, Point, great_circle_distance},
+    photo_finder::is_supported_photo,
+    timeline::Timeline,
+    xmp,
+};
 
 /// Convert decimal degrees to degrees, minutes, seconds
 fn decimal_to_dms(decimal: f64) -> (u32, u32, f64) {
@@ -33,90 +45,551 @@ fn get_longitude_ref(longitude: f64) -> String {
     }
 }
 
+/// Where the GPS position of a photo is written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum WriteMode {
+    /// EXIF tags inside the photo itself
+    Exif,
+    /// An XMP sidecar next to the photo, leaving the photo untouched
+    Xmp,
+    /// Both EXIF tags and an XMP sidecar
+    Both,
+}
+
+impl WriteMode {
+    pub fn writes_exif(&self) -> bool {
+        matches!(self, WriteMode::Exif | WriteMode::Both)
+    }
+
+    pub fn writes_sidecar(&self) -> bool {
+        matches!(self, WriteMode::Xmp | WriteMode::Both)
+    }
+}
+
+/// What to do with photos that already carry GPS tags
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExistingGpsPolicy {
+    /// Leave the existing tags alone
+    Skip,
+    /// Always replace them with the timeline position
+    Overwrite,
+    /// Replace them only when they are far from the timeline position
+    IfFar,
+}
+
+impl fmt::Display for ExistingGpsPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExistingGpsPolicy::Skip => write!(f, "skip"),
+            ExistingGpsPolicy::Overwrite => write!(f, "overwrite"),
+            ExistingGpsPolicy::IfFar => write!(f, "if-far"),
+        }
+    }
+}
+
+/// How a photo's local capture time was turned into UTC
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeMethod {
+    /// The photo's own `OffsetTimeOriginal` EXIF tag
+    ExifOffset(FixedOffset),
+    /// The UTC offset the timeline recorded around the capture time
+    TimelineOffset(FixedOffset),
+    /// The timezone given for the whole batch
+    Timezone(Tz),
+}
+
+impl fmt::Display for TimeMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimeMethod::ExifOffset(offset) => write!(f, "EXIF offset {}", offset),
+            TimeMethod::TimelineOffset(offset) => write!(f, "timeline offset {}", offset),
+            TimeMethod::Timezone(timezone) => write!(f, "timezone {}", timezone),
+        }
+    }
+}
+
+/// Settings shared by every photo in a batch
+pub struct GeotagOptions {
+    /// Timezone the camera clock was set to
+    pub photo_timezone: Tz,
+    /// Take each photo's UTC offset from the timeline, falling back to
+    /// `photo_timezone` when the timeline is ambiguous
+    pub auto_timezone: bool,
+    /// Corrections for camera clocks that are off, applied before the
+    /// capture time is looked up in the timeline
+    pub clock_offsets: ClockOffsets,
+    /// Report what would be written without modifying any files
+    pub dry_run: bool,
+    pub write_mode: WriteMode,
+    pub existing_gps: ExistingGpsPolicy,
+    /// Distance in meters beyond which `ExistingGpsPolicy::IfFar` overwrites
+    pub far_distance: f64,
+    /// Limits on the time between the fixes a position is interpolated from
+    pub max_gap: MaxGap,
+}
+
+/// What happened to a single photo
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PhotoStatus {
+    /// GPS tags were written (or would have been, in a dry run)
+    Tagged,
+    /// The photo was deliberately left alone
+    Skipped,
+    /// The timeline has no position for the photo's capture time
+    NoLocation,
+    /// The nearest positions are too far away in time to interpolate between
+    NoReliableLocation,
+    /// The photo's metadata or capture time could not be read
+    Unreadable,
+    /// The location was found but writing the metadata failed
+    WriteFailed,
+}
+
+impl PhotoStatus {
+    pub fn is_failure(&self) -> bool {
+        !matches!(self, PhotoStatus::Tagged | PhotoStatus::Skipped)
+    }
+}
+
+impl fmt::Display for PhotoStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PhotoStatus::Tagged => write!(f, "tagged"),
+            PhotoStatus::Skipped => write!(f, "skipped"),
+            PhotoStatus::NoLocation => write!(f, "no location"),
+            PhotoStatus::NoReliableLocation => write!(f, "no reliable location"),
+            PhotoStatus::Unreadable => write!(f, "unreadable"),
+            PhotoStatus::WriteFailed => write!(f, "write failed"),
+        }
+    }
+}
+
+/// Outcome of geotagging a single photo
+#[derive(Debug)]
+pub struct PhotoResult {
+    pub path: PathBuf,
+    pub status: PhotoStatus,
+    /// Human-readable detail, e.g. the coordinates written or why it failed
+    pub message: String,
+    /// The policy applied because the photo already had GPS tags
+    pub existing_gps_policy: Option<ExistingGpsPolicy>,
+    /// How the capture time was converted to UTC, once it was known
+    pub time_method: Option<TimeMethod>,
+}
+
+impl PhotoResult {
+    fn new(path: &Path, status: PhotoStatus, message: String) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            status,
+            message,
+            existing_gps_policy: None,
+            time_method: None,
+        }
+    }
+
+    fn with_time_method(mut self, time_method: TimeMethod, clock_offset: TimeDelta) -> Self {
+        self.message = if clock_offset.is_zero() {
+            format!("{} (time from {})", self.message, time_method)
+        } else {
+            format!(
+                "{} (time from {}, clock offset {})",
+                self.message,
+                time_method,
+                format_clock_offset(clock_offset)
+            )
+        };
+        self.time_method = Some(time_method);
+        self
+    }
+
+    fn with_policy(mut self, policy: Option<ExistingGpsPolicy>) -> Self {
+        if let Some(policy) = policy {
+            self.message = format!("{} (existing GPS, policy: {})", self.message, policy);
+        }
+        self.existing_gps_policy = policy;
+        self
+    }
+}
+
+/// Outcome of geotagging a whole batch of photos
+#[derive(Debug, Default)]
+pub struct GeotagSummary {
+    pub results: Vec<PhotoResult>,
+}
+
+impl GeotagSummary {
+    pub fn count(&self, status: PhotoStatus) -> usize {
+        self.results
+            .iter()
+            .filter(|result| result.status == status)
+            .count()
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = &PhotoResult> {
+        self.results
+            .iter()
+            .filter(|result| result.status.is_failure())
+    }
+
+    pub fn has_failures(&self) -> bool {
+        self.failures().next().is_some()
+    }
+}
+
+impl fmt::Display for GeotagSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let statuses = [
+            PhotoStatus::Tagged,
+            PhotoStatus::Skipped,
+            PhotoStatus::NoLocation,
+            PhotoStatus::NoReliableLocation,
+            PhotoStatus::Unreadable,
+            PhotoStatus::WriteFailed,
+        ];
+
+        let counts = statuses
+            .iter()
+            .map(|status| format!("{}: {}", status, self.count(*status)))
+            .collect::<Vec<_>>();
+
+        write!(f, "{}", counts.join(", "))
+    }
+}
+
 pub fn geotag_photos(
     timeline: &Timeline,
-    photos_path: &Path,
-    photo_timezone: Tz,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let photos = fs::read_dir(photos_path)?
-        .filter_map(Result::ok)
-        .filter(|entry| {
-            entry.path().is_file()
-                && entry.path().extension().map_or(false, |ext| {
-                    let string = ext.to_string_lossy().to_lowercase();
-                    string == "jpg" || string == "jpeg" || string == "png"
-                })
-        })
-        .collect::<Vec<_>>();
-
-    if photos.is_empty() {
-        return Err("No photos found in the specified directory".into());
-    }
+    photos: &[PathBuf],
+    options: &GeotagOptions,
+    mut journal: Option<&mut Journal>,
+) -> GeotagSummary {
+    let mut summary = GeotagSummary::default();
 
     for photo in photos {
-        geotag_photo(timeline, &photo.path(), photo_timezone)?;
+        let result = geotag_photo(timeline, photo, options, journal.as_deref_mut());
+
+        if result.status.is_failure() {
+            eprintln!("{}", result.message);
+        } else {
+            println!("{}", result.message);
+        }
+
+        summary.results.push(result);
     }
 
-    Ok(())
+    summary
 }
 
 fn geotag_photo(
     timeline: &Timeline,
     photo_path: &Path,
-    photo_timezone: Tz,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let extension = photo_path
-        .extension()
-        .and_then(|ext| ext.to_str())
-        .unwrap_or("");
+    options: &GeotagOptions,
+    journal: Option<&mut Journal>,
+) -> PhotoResult {
+    if !is_supported_photo(photo_path) {
+        return PhotoResult::new(
+            photo_path,
+            PhotoStatus::Skipped,
+            format!("Skipping non-image file: {}", photo_path.display()),
+        );
+    }
 
-    if !["jpg", "jpeg", "png"].contains(&extension.to_lowercase().as_str()) {
-        println!("Skipping non-image file: {}", photo_path.display());
-        return Ok(());
+    let Ok(mut metadata) = Metadata::new_from_path(photo_path) else {
+        return PhotoResult::new(
+            photo_path,
+            PhotoStatus::Unreadable,
+            format!(
+                "Failed to read metadata for photo: {}",
+                photo_path.display()
+            ),
+        );
+    };
+
+    let existing_position = existing_position(photo_path, &metadata, options.write_mode);
+    let policy = existing_position.map(|_| options.existing_gps);
+
+    if policy == Some(ExistingGpsPolicy::Skip) {
+        return PhotoResult::new(
+            photo_path,
+            PhotoStatus::Skipped,
+            format!(
+                "Skipping photo that already has GPS tags: {}",
+                photo_path.display()
+            ),
+        )
+        .with_policy(policy);
     }
 
-    let metadata = Metadata::new_from_path(photo_path);
+    let (local_time, exif_offset) = match read_photo_time(&metadata) {
+        Ok(photo_time) => photo_time,
+        Err(e) => {
+            return PhotoResult::new(
+                photo_path,
+                PhotoStatus::Unreadable,
+                format!("{} for photo {}", e, photo_path.display()),
+            );
+        }
+    };
 
-    if metadata.is_err() {
-        return Err(format!(
-            "Failed to read metadata for photo: {}",
-            photo_path.display()
+    let clock_offset = options.clock_offsets.for_camera(&Camera::read(&metadata));
+    let local_time = local_time + clock_offset;
+
+    let (photo_time, time_method) =
+        match resolve_photo_time(timeline, &local_time, exif_offset, options) {
+            Ok(resolved) => resolved,
+            Err(e) => {
+                return PhotoResult::new(
+                    photo_path,
+                    PhotoStatus::Unreadable,
+                    format!("{} for photo {}", e, photo_path.display()),
+                );
+            }
+        };
+
+    let line = match timeline.get_line_within(&photo_time, &options.max_gap) {
+        Some(Err(reason)) => {
+            return PhotoResult::new(
+                photo_path,
+                PhotoStatus::NoReliableLocation,
+                format!(
+                    "No reliable location for photo {} at time {}: {}",
+                    photo_path.display(),
+                    photo_time,
+                    reason
+                ),
+            )
+            .with_time_method(time_method, clock_offset);
+        }
+        Some(Ok(line)) => Some(line),
+        None => None,
+    };
+    let result = line.as_ref().map(|line| line.get_point_at(&photo_time));
+
+    let (Some(line), Some(Ok(point))) = (line.as_ref(), result) else {
+        return PhotoResult::new(
+            photo_path,
+            PhotoStatus::NoLocation,
+            format!(
+                "No location found for photo {} at time {}",
+                photo_path.display(),
+                photo_time
+            ),
         )
-        .into());
+        .with_time_method(time_method, clock_offset);
+    };
+
+    if let Some(existing_position) = existing_position
+        && options.existing_gps == ExistingGpsPolicy::IfFar
+    {
+        let distance = great_circle_distance(existing_position, (point.lat, point.lng));
+        if distance <= options.far_distance {
+            return PhotoResult::new(
+                photo_path,
+                PhotoStatus::Skipped,
+                format!(
+                    "Skipping photo whose GPS tags are {:.0} m from the timeline position: {}",
+                    distance,
+                    photo_path.display()
+                ),
+            )
+            .with_time_method(time_method, clock_offset)
+            .with_policy(policy);
+        }
     }
 
-    let mut metadata = metadata.unwrap();
-    let photo_time = metadata
-        .get_tag(&ExifTag::DateTimeOriginal(String::new()))
-        .next();
+    if options.dry_run {
+        return PhotoResult::new(
+            photo_path,
+            PhotoStatus::Tagged,
+            format!(
+                "Would tag photo {} with {:.7}, {:.7} ±{:.0} m from {} ({}s before, {}s after)",
+                photo_path.display(),
+                point.lat,
+                point.lng,
+                line.estimated_error(),
+                point.source,
+                line.gap_before(),
+                line.gap_after()
+            ),
+        )
+        .with_time_method(time_method, clock_offset)
+        .with_policy(policy);
+    }
+
+    let mut written = Vec::new();
+
+    if options.write_mode.writes_sidecar() {
+        match xmp::write_sidecar(photo_path, &point, &photo_time) {
+            Ok(sidecar_path) => written.push(format!("XMP sidecar {}", sidecar_path.display())),
+            Err(e) => {
+                return PhotoResult::new(
+                    photo_path,
+                    PhotoStatus::WriteFailed,
+                    format!(
+                        "Failed to write XMP sidecar for photo {}: {}",
+                        photo_path.display(),
+                        e
+                    ),
+                )
+                .with_time_method(time_method, clock_offset)
+                .with_policy(policy);
+            }
+        }
+    }
+
+    if options.write_mode.writes_exif() {
+        if let Some(journal) = journal
+            && let Err(e) = journal.record(photo_path, &metadata)
+        {
+            return PhotoResult::new(
+                photo_path,
+                PhotoStatus::WriteFailed,
+                format!("Failed to back up photo {}: {}", photo_path.display(), e),
+            )
+            .with_time_method(time_method, clock_offset)
+            .with_policy(policy);
+        }
 
-    let Some(ExifTag::DateTimeOriginal(photo_time)) = photo_time else {
-        return Err(format!(
-            "Photo {} does not have DateTimeOriginal tag",
+        set_gps_tags(&mut metadata, &point, &photo_time);
+
+        if let Err(e) = metadata.write_to_file(photo_path) {
+            return PhotoResult::new(
+                photo_path,
+                PhotoStatus::WriteFailed,
+                format!(
+                    "Failed to write metadata to photo {}: {}",
+                    photo_path.display(),
+                    e
+                ),
+            )
+            .with_time_method(time_method, clock_offset)
+            .with_policy(policy);
+        }
+
+        written.push("EXIF tags".to_string());
+    }
+
+    PhotoResult::new(
+        photo_path,
+        PhotoStatus::Tagged,
+        format!(
+            "Successfully wrote {} for photo: {}",
+            written.join(" and "),
             photo_path.display()
-        )
-        .into());
+        ),
+    )
+    .with_time_method(time_method, clock_offset)
+    .with_policy(policy)
+}
+
+/// The GPS position the photo already has: its EXIF tags, or when writing a
+/// sidecar, the position already in the sidecar
+fn existing_position(
+    photo_path: &Path,
+    metadata: &Metadata,
+    write_mode: WriteMode,
+) -> Option<(f64, f64)> {
+    GpsTags::read(metadata).position().or_else(|| {
+        write_mode
+            .writes_sidecar()
+            .then(|| xmp::read_sidecar_position(photo_path))
+            .flatten()
+    })
+}
+
+/// Read the local capture time, including `SubSecTimeOriginal`, and the UTC
+/// offset from `OffsetTimeOriginal` if the camera recorded one
+pub fn read_photo_time(
+    metadata: &Metadata,
+) -> Result<(NaiveDateTime, Option<FixedOffset>), String> {
+    let Some(ExifTag::DateTimeOriginal(date_time)) = metadata
+        .get_tag(&ExifTag::DateTimeOriginal(String::new()))
+        .next()
+    else {
+        return Err("Missing DateTimeOriginal tag".to_string());
     };
 
-    let photo_time = NaiveDateTime::parse_from_str(&photo_time, "%Y:%m:%d %H:%M:%S")
-        .map_err(|e| format!("Failed to parse photo time: {}", e))?
-        .and_local_timezone(photo_timezone)
-        .unwrap()
-        .to_utc();
+    let mut local = NaiveDateTime::parse_from_str(date_time.trim(), "%Y:%m:%d %H:%M:%S")
+        .map_err(|e| format!("Failed to parse photo time {}: {}", date_time, e))?;
 
-    let result = timeline.get_point_at(&photo_time);
+    if let Some(ExifTag::SubSecTimeOriginal(sub_sec)) = metadata
+        .get_tag(&ExifTag::SubSecTimeOriginal(String::new()))
+        .next()
+        && let Some(fraction) = parse_sub_sec(sub_sec)
+    {
+        local += fraction;
+    }
 
-    if result.is_err() {
-        return Err(format!(
-            "No location found for photo {} at time {}",
-            photo_path.display(),
-            photo_time
-        )
-        .into());
+    let offset = match metadata
+        .get_tag(&ExifTag::OffsetTimeOriginal(String::new()))
+        .next()
+    {
+        Some(ExifTag::OffsetTimeOriginal(offset)) => parse_exif_offset(offset),
+        _ => None,
+    };
+
+    Ok((local, offset))
+}
+
+/// Parse `SubSecTimeOriginal`, the decimal digits following the seconds
+fn parse_sub_sec(sub_sec: &str) -> Option<TimeDelta> {
+    let digits = sub_sec.trim_matches(|c: char| c.is_whitespace() || c == '\0');
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
     }
 
-    let point = result.unwrap();
+    // Keep nanosecond precision and pad to nine digits
+    let digits = &digits[..digits.len().min(9)];
+    let nanos = digits.parse::<i64>().ok()? * 10_i64.pow(9 - digits.len() as u32);
+    Some(TimeDelta::nanoseconds(nanos))
+}
+
+/// Parse `OffsetTimeOriginal` (e.g. `+02:00`); blank values mean unknown
+fn parse_exif_offset(offset: &str) -> Option<FixedOffset> {
+    offset
+        .trim_matches(|c: char| c.is_whitespace() || c == '\0')
+        .parse()
+        .ok()
+}
 
+/// Convert a photo's local capture time to UTC, reporting how it was done.
+/// The photo's own offset wins, then the timeline's, then the batch timezone.
+pub fn resolve_photo_time(
+    timeline: &Timeline,
+    local: &NaiveDateTime,
+    exif_offset: Option<FixedOffset>,
+    options: &GeotagOptions,
+) -> Result<(DateTime<Utc>, TimeMethod), String> {
+    if let Some(offset) = exif_offset
+        && let Some(photo_time) = local.and_local_timezone(offset).single()
+    {
+        return Ok((photo_time.to_utc(), TimeMethod::ExifOffset(offset)));
+    }
+
+    if options.auto_timezone
+        && let Some(offset) = timeline.utc_offset_at_local(local)
+        && let Some(photo_time) = local.and_local_timezone(offset).single()
+    {
+        return Ok((photo_time.to_utc(), TimeMethod::TimelineOffset(offset)));
+    }
+
+    let photo_time = local
+        .and_local_timezone(options.photo_timezone)
+        .earliest()
+        .ok_or_else(|| {
+            format!(
+                "Photo time {} does not exist in timezone {}",
+                local, options.photo_timezone
+            )
+        })?;
+
+    Ok((
+        photo_time.to_utc(),
+        TimeMethod::Timezone(options.photo_timezone),
+    ))
+}
+
+fn set_gps_tags(metadata: &mut Metadata, point: &Point, photo_time: &DateTime<Utc>) {
     // Convert decimal degrees to DMS format (required by EXIF GPS standard)
     // GPS coordinates must be stored as degrees, minutes, seconds in rational format
     let (lat_degrees, lat_minutes, lat_seconds) = decimal_to_dms(point.lat);
@@ -144,6 +617,9 @@ fn geotag_photo(
             1 // Below sea level
         }]));
     }
+    if let Some(error) = point.estimated_error {
+        metadata.set_tag(ExifTag::GPSHPositioningError(vec![error.into()]));
+    }
     metadata.set_tag(ExifTag::GPSTimeStamp(vec![
         photo_time.hour().into(),
         photo_time.minute().into(),
@@ -153,14 +629,103 @@ fn geotag_photo(
         photo_time.date_naive().format("%Y:%m:%d").to_string(),
     ));
     metadata.set_tag(ExifTag::GPSVersionID(vec![2, 2, 0, 0]));
+}
 
-    match metadata.write_to_file(photo_path) {
-        Ok(()) => println!(
-            "Successfully wrote metadata to photo: {}",
-            photo_path.display()
-        ),
-        Err(e) => eprintln!("Failed to write metadata to photo: {}", e),
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary() {
+        let mut summary = GeotagSummary::default();
+        for (name, status) in [
+            ("a.jpg", PhotoStatus::Tagged),
+            ("b.jpg", PhotoStatus::Tagged),
+            ("c.jpg", PhotoStatus::NoLocation),
+            ("d.jpg", PhotoStatus::Skipped),
+        ] {
+            summary
+                .results
+                .push(PhotoResult::new(Path::new(name), status, String::new()));
+        }
+
+        assert_eq!(summary.count(PhotoStatus::Tagged), 2);
+        assert_eq!(summary.count(PhotoStatus::WriteFailed), 0);
+        assert!(summary.has_failures());
+        assert_eq!(
+            summary
+                .failures()
+                .map(|result| result.path.clone())
+                .collect::<Vec<_>>(),
+            vec![PathBuf::from("c.jpg")]
+        );
+        assert_eq!(
+            summary.to_string(),
+            "tagged: 2, skipped: 1, no location: 1, no reliable location: 0, unreadable: 0, write failed: 0"
+        );
+    }
+
+    #[test]
+    fn test_resolve_photo_time() {
+        let timeline = Timeline::from_path("tests/basic_example.json").unwrap();
+        let mut options = GeotagOptions {
+            photo_timezone: chrono_tz::Europe::London,
+            auto_timezone: true,
+            clock_offsets: ClockOffsets::default(),
+            dry_run: true,
+            write_mode: WriteMode::Exif,
+            existing_gps: ExistingGpsPolicy::Skip,
+            far_distance: 500.0,
+            max_gap: MaxGap::default(),
+        };
+
+        // The timeline recorded +02:00 for this time
+        let local =
+            NaiveDateTime::parse_from_str("2023:08:29 13:20:00", "%Y:%m:%d %H:%M:%S").unwrap();
+        let (photo_time, method) = resolve_photo_time(&timeline, &local, None, &options).unwrap();
+        assert_eq!(photo_time.to_rfc3339(), "2023-08-29T11:20:00+00:00");
+        assert_eq!(
+            method,
+            TimeMethod::TimelineOffset(FixedOffset::east_opt(2 * 3600).unwrap())
+        );
+
+        // Outside the timeline the given timezone is used
+        let local =
+            NaiveDateTime::parse_from_str("2020:07:01 10:00:00", "%Y:%m:%d %H:%M:%S").unwrap();
+        let (photo_time, method) = resolve_photo_time(&timeline, &local, None, &options).unwrap();
+        assert_eq!(photo_time.to_rfc3339(), "2020-07-01T09:00:00+00:00");
+        assert_eq!(method, TimeMethod::Timezone(chrono_tz::Europe::London));
+
+        options.auto_timezone = false;
+        let local =
+            NaiveDateTime::parse_from_str("2023:08:29 13:20:00", "%Y:%m:%d %H:%M:%S").unwrap();
+        let (photo_time, _) = resolve_photo_time(&timeline, &local, None, &options).unwrap();
+        assert_eq!(photo_time.to_rfc3339(), "2023-08-29T12:20:00+00:00");
+
+        // The photo's own offset takes precedence
+        let offset = FixedOffset::west_opt(5 * 3600).unwrap();
+        let (photo_time, method) =
+            resolve_photo_time(&timeline, &local, Some(offset), &options).unwrap();
+        assert_eq!(photo_time.to_rfc3339(), "2023-08-29T18:20:00+00:00");
+        assert_eq!(method, TimeMethod::ExifOffset(offset));
     }
 
-    Ok(())
+    #[test]
+    fn test_read_photo_time() {
+        let mut metadata = Metadata::new();
+        assert!(read_photo_time(&metadata).is_err());
+
+        metadata.set_tag(ExifTag::DateTimeOriginal("2023:08:29 13:20:00".to_string()));
+        metadata.set_tag(ExifTag::SubSecTimeOriginal("25".to_string()));
+        metadata.set_tag(ExifTag::OffsetTimeOriginal("+02:00".to_string()));
+        let (local, offset) = read_photo_time(&metadata).unwrap();
+        assert_eq!(local.to_string(), "2023-08-29 13:20:00.250");
+        assert_eq!(offset, FixedOffset::east_opt(2 * 3600));
+
+        metadata.set_tag(ExifTag::SubSecTimeOriginal("   ".to_string()));
+        metadata.set_tag(ExifTag::OffsetTimeOriginal("   :  ".to_string()));
+        let (local, offset) = read_photo_time(&metadata).unwrap();
+        assert_eq!(local.to_string(), "2023-08-29 13:20:00");
+        assert_eq!(offset, None);
+    }
 }
@@ -0,0 +1,227 @@
+use little_exif::{exif_tag::ExifTag, metadata::Metadata, rational::uR64};
+use serde::{Deserialize, Serialize};
+
+/// Snapshot of every GPS tag the geotagger writes, as found in a photo.
+/// Missing tags are `None` so they can be removed again when restoring.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct GpsTags {
+    pub version_id: Option<Vec<u8>>,
+    pub latitude_ref: Option<String>,
+    /// Rationals stored as `[numerator, denominator]` to round-trip exactly
+    pub latitude: Option<Vec<[u32; 2]>>,
+    pub longitude_ref: Option<String>,
+    pub longitude: Option<Vec<[u32; 2]>>,
+    pub altitude_ref: Option<Vec<u8>>,
+    pub altitude: Option<Vec<[u32; 2]>>,
+    pub time_stamp: Option<Vec<[u32; 2]>>,
+    pub date_stamp: Option<String>,
+    pub h_positioning_error: Option<Vec<[u32; 2]>>,
+}
+
+impl GpsTags {
+    pub fn read(metadata: &Metadata) -> Self {
+        let mut tags = GpsTags::default();
+
+        let queries = [
+            ExifTag::GPSVersionID(Vec::new()),
+            ExifTag::GPSLatitudeRef(String::new()),
+            ExifTag::GPSLatitude(Vec::new()),
+            ExifTag::GPSLongitudeRef(String::new()),
+            ExifTag::GPSLongitude(Vec::new()),
+            ExifTag::GPSAltitudeRef(Vec::new()),
+            ExifTag::GPSAltitude(Vec::new()),
+            ExifTag::GPSTimeStamp(Vec::new()),
+            ExifTag::GPSDateStamp(String::new()),
+            ExifTag::GPSHPositioningError(Vec::new()),
+        ];
+
+        for query in queries {
+            match metadata.get_tag(&query).next() {
+                Some(ExifTag::GPSVersionID(value)) => tags.version_id = Some(value.clone()),
+                Some(ExifTag::GPSLatitudeRef(value)) => tags.latitude_ref = Some(value.clone()),
+                Some(ExifTag::GPSLatitude(value)) => tags.latitude = Some(to_pairs(value)),
+                Some(ExifTag::GPSLongitudeRef(value)) => tags.longitude_ref = Some(value.clone()),
+                Some(ExifTag::GPSLongitude(value)) => tags.longitude = Some(to_pairs(value)),
+                Some(ExifTag::GPSAltitudeRef(value)) => tags.altitude_ref = Some(value.clone()),
+                Some(ExifTag::GPSAltitude(value)) => tags.altitude = Some(to_pairs(value)),
+                Some(ExifTag::GPSTimeStamp(value)) => tags.time_stamp = Some(to_pairs(value)),
+                Some(ExifTag::GPSDateStamp(value)) => tags.date_stamp = Some(value.clone()),
+                Some(ExifTag::GPSHPositioningError(value)) => {
+                    tags.h_positioning_error = Some(to_pairs(value))
+                }
+                _ => {}
+            }
+        }
+
+        tags
+    }
+
+    /// Decimal latitude and longitude, if the photo has a complete position
+    pub fn position(&self) -> Option<(f64, f64)> {
+        let lat = dms_to_decimal(self.latitude.as_deref()?)?;
+        let lng = dms_to_decimal(self.longitude.as_deref()?)?;
+
+        let lat = match self.latitude_ref.as_deref().map(str::trim) {
+            Some("S") => -lat,
+            _ => lat,
+        };
+        let lng = match self.longitude_ref.as_deref().map(str::trim) {
+            Some("W") => -lng,
+            _ => lng,
+        };
+
+        Some((lat, lng))
+    }
+
+    /// Put the snapshot back, removing tags that were absent when it was taken
+    pub fn restore(&self, metadata: &mut Metadata) {
+        restore_tag(
+            metadata,
+            self.version_id.clone().map(ExifTag::GPSVersionID),
+            ExifTag::GPSVersionID(Vec::new()),
+        );
+        restore_tag(
+            metadata,
+            self.latitude_ref.clone().map(ExifTag::GPSLatitudeRef),
+            ExifTag::GPSLatitudeRef(String::new()),
+        );
+        restore_tag(
+            metadata,
+            self.latitude
+                .as_deref()
+                .map(from_pairs)
+                .map(ExifTag::GPSLatitude),
+            ExifTag::GPSLatitude(Vec::new()),
+        );
+        restore_tag(
+            metadata,
+            self.longitude_ref.clone().map(ExifTag::GPSLongitudeRef),
+            ExifTag::GPSLongitudeRef(String::new()),
+        );
+        restore_tag(
+            metadata,
+            self.longitude
+                .as_deref()
+                .map(from_pairs)
+                .map(ExifTag::GPSLongitude),
+            ExifTag::GPSLongitude(Vec::new()),
+        );
+        restore_tag(
+            metadata,
+            self.altitude_ref.clone().map(ExifTag::GPSAltitudeRef),
+            ExifTag::GPSAltitudeRef(Vec::new()),
+        );
+        restore_tag(
+            metadata,
+            self.altitude
+                .as_deref()
+                .map(from_pairs)
+                .map(ExifTag::GPSAltitude),
+            ExifTag::GPSAltitude(Vec::new()),
+        );
+        restore_tag(
+            metadata,
+            self.time_stamp
+                .as_deref()
+                .map(from_pairs)
+                .map(ExifTag::GPSTimeStamp),
+            ExifTag::GPSTimeStamp(Vec::new()),
+        );
+        restore_tag(
+            metadata,
+            self.date_stamp.clone().map(ExifTag::GPSDateStamp),
+            ExifTag::GPSDateStamp(String::new()),
+        );
+        restore_tag(
+            metadata,
+            self.h_positioning_error
+                .as_deref()
+                .map(from_pairs)
+                .map(ExifTag::GPSHPositioningError),
+            ExifTag::GPSHPositioningError(Vec::new()),
+        );
+    }
+}
+
+fn restore_tag(metadata: &mut Metadata, tag: Option<ExifTag>, empty: ExifTag) {
+    match tag {
+        Some(tag) => metadata.set_tag(tag),
+        None => {
+            metadata.remove_tag(empty);
+        }
+    }
+}
+
+/// Convert degrees, minutes and seconds rationals to decimal degrees
+fn dms_to_decimal(pairs: &[[u32; 2]]) -> Option<f64> {
+    let mut decimal = 0.0;
+    for ([numerator, denominator], scale) in pairs.iter().zip([1.0, 60.0, 3600.0]) {
+        if *denominator == 0 {
+            return None;
+        }
+        decimal += *numerator as f64 / *denominator as f64 / scale;
+    }
+    Some(decimal)
+}
+
+fn to_pairs(rationals: &[uR64]) -> Vec<[u32; 2]> {
+    rationals
+        .iter()
+        .map(|rational| [rational.nominator, rational.denominator])
+        .collect()
+}
+
+fn from_pairs(pairs: &[[u32; 2]]) -> Vec<uR64> {
+    pairs
+        .iter()
+        .map(|[nominator, denominator]| uR64 {
+            nominator: *nominator,
+            denominator: *denominator,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_and_restore() {
+        let mut metadata = Metadata::new();
+        metadata.set_tag(ExifTag::GPSLatitudeRef("N".to_string()));
+        metadata.set_tag(ExifTag::GPSLatitude(from_pairs(&[
+            [50, 1],
+            [8, 1],
+            [4121, 100],
+        ])));
+
+        let original = GpsTags::read(&metadata);
+        assert_eq!(original.latitude_ref.as_deref(), Some("N"));
+        assert_eq!(original.latitude, Some(vec![[50, 1], [8, 1], [4121, 100]]));
+        assert_eq!(original.longitude, None);
+
+        metadata.set_tag(ExifTag::GPSLatitude(from_pairs(&[[1, 1], [2, 1], [3, 1]])));
+        metadata.set_tag(ExifTag::GPSLongitudeRef("W".to_string()));
+        metadata.set_tag(ExifTag::GPSLongitude(from_pairs(&[[1, 1], [2, 1], [3, 1]])));
+        assert_ne!(GpsTags::read(&metadata), original);
+
+        original.restore(&mut metadata);
+        assert_eq!(GpsTags::read(&metadata), original);
+    }
+
+    #[test]
+    fn test_position() {
+        let mut tags = GpsTags {
+            latitude_ref: Some("N".to_string()),
+            latitude: Some(vec![[50, 1], [8, 1], [4121, 100]]),
+            longitude_ref: Some("W".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(tags.position(), None);
+
+        tags.longitude = Some(vec![[5, 1], [36, 1], [540, 100]]);
+        let (lat, lng) = tags.position().unwrap();
+        assert!((lat - 50.1447805556).abs() < 1e-9);
+        assert!((lng - -5.6015).abs() < 1e-9);
+    }
+}
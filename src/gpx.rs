@@ -0,0 +1,155 @@
+use chrono::DateTime;
+use quick_xml::{Reader, events::Event};
+use std::error::Error;
+
+use crate::{line::PointSource, track::TrackPoint};
+
+/// A `trkpt` or `wpt` being read, until its end tag
+#[derive(Default)]
+struct PendingPoint {
+    lat: Option<f64>,
+    lng: Option<f64>,
+    altitude: Option<f64>,
+    time: Option<String>,
+}
+
+impl PendingPoint {
+    fn into_track_point(self) -> Option<TrackPoint> {
+        let timestamp = DateTime::parse_from_rfc3339(self.time?.trim()).ok()?;
+
+        Some(TrackPoint {
+            lat: self.lat?,
+            lng: self.lng?,
+            altitude: self.altitude,
+            accuracy: None,
+            speed: None,
+            timestamp: timestamp.to_utc(),
+            source: PointSource::Gpx,
+        })
+    }
+}
+
+/// Element whose text is being read inside a point
+#[derive(Clone, Copy, PartialEq)]
+enum Field {
+    None,
+    Elevation,
+    Time,
+}
+
+fn is_point(name: &str) -> bool {
+    matches!(local_name(name), "trkpt" | "wpt")
+}
+
+/// Strip any namespace prefix, e.g. `gpx:trkpt`
+fn local_name(name: &str) -> &str {
+    name.rsplit_once(':').map_or(name, |(_, local)| local)
+}
+
+/// Parse the track points and waypoints of a GPX file. Points without a
+/// time are skipped, since they can't be matched to a photo.
+pub fn parse(xml: &str) -> Result<Vec<TrackPoint>, Box<dyn Error>> {
+    let mut reader = Reader::from_str(xml);
+    let mut points = Vec::new();
+    let mut pending: Option<PendingPoint> = None;
+    let mut field = Field::None;
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(element) if is_point(element.name().as_ref()) => {
+                let mut point = PendingPoint::default();
+                for attribute in element.attributes() {
+                    let attribute = attribute?;
+                    match attribute.key.as_ref() {
+                        "lat" => point.lat = attribute.value.trim().parse().ok(),
+                        "lon" => point.lng = attribute.value.trim().parse().ok(),
+                        _ => {}
+                    }
+                }
+                pending = Some(point);
+            }
+            Event::Start(element) if pending.is_some() => {
+                field = match local_name(element.name().as_ref()) {
+                    "ele" => Field::Elevation,
+                    "time" => Field::Time,
+                    _ => Field::None,
+                };
+            }
+            Event::Text(text) if field != Field::None => {
+                let text = text.xml10_content();
+                if let Some(point) = pending.as_mut() {
+                    match field {
+                        Field::Elevation => point.altitude = text.trim().parse().ok(),
+                        Field::Time => point.time = Some(text.to_string()),
+                        Field::None => {}
+                    }
+                }
+            }
+            Event::End(element) if is_point(element.name().as_ref()) => {
+                if let Some(point) = pending.take().and_then(PendingPoint::into_track_point) {
+                    points.push(point);
+                }
+                field = Field::None;
+            }
+            Event::End(_) => field = Field::None,
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(points)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timeline::Timeline;
+    use chrono::Utc;
+
+    #[test]
+    fn test_parse() {
+        let xml = std::fs::read_to_string("tests/track_example.gpx").unwrap();
+        let points = parse(&xml).unwrap();
+
+        assert_eq!(points.len(), 4);
+        assert_eq!(points[0].lat, 54.79737);
+        assert_eq!(points[0].altitude, None);
+        assert_eq!(points[1].lat, 54.7768);
+        assert_eq!(points[1].lng, -1.5757);
+        assert_eq!(points[1].altitude, Some(42.5));
+        assert_eq!(
+            points[1].timestamp.to_rfc3339(),
+            "2025-08-11T15:20:00+00:00"
+        );
+        assert_eq!(
+            points[2].timestamp.to_rfc3339(),
+            "2025-08-11T15:21:00+00:00"
+        );
+        assert_eq!(points[3].source, PointSource::Gpx);
+    }
+
+    #[test]
+    fn test_gpx_with_timeline() {
+        let mut data = Timeline::from_path("tests/basic_example.json").unwrap();
+        data.merge(Timeline::from_path("tests/track_example.gpx").unwrap());
+
+        // Only the GPX track covers this time
+        let timestamp = DateTime::parse_from_rfc3339("2025-08-11T15:21:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let line = data.get_line_at(&timestamp).unwrap();
+        assert_eq!(line.start.source, PointSource::Gpx);
+        assert_eq!(line.start.relative_seconds, -60);
+        assert_eq!(line.end.relative_seconds, 60);
+        assert_eq!(line.end.altitude, Some(44.0));
+
+        // The GPX waypoint is closer in time than the nearest raw signal
+        let timestamp = DateTime::parse_from_rfc3339("2025-08-11T15:25:56Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let line = data.get_line_at(&timestamp).unwrap();
+        assert_eq!(line.start.source, PointSource::RawSignal);
+        assert_eq!(line.end.source, PointSource::Gpx);
+        assert_eq!(line.end.relative_seconds, 2);
+    }
+}
@@ -0,0 +1,250 @@
+use chrono::Utc;
+use clap::ValueEnum;
+use little_exif::metadata::Metadata;
+use serde::{Deserialize, Serialize};
+use std::{
+    error::Error,
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::gps_tags::GpsTags;
+
+const JOURNAL_FILE: &str = "journal.jsonl";
+const FILES_DIR: &str = "files";
+
+/// What is saved before a photo's EXIF tags are overwritten
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum BackupMode {
+    /// Only the photo's original GPS tags
+    Gps,
+    /// A copy of the whole original file
+    File,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum Backup {
+    Gps { tags: Box<GpsTags> },
+    File { copy: PathBuf },
+}
+
+/// One line of the journal, written before the photo is modified
+#[derive(Serialize, Deserialize, Debug)]
+struct JournalEntry {
+    photo: PathBuf,
+    #[serde(flatten)]
+    backup: Backup,
+}
+
+/// Write-ahead record of a single geotagging run, stored in its own
+/// directory so the run can be undone later. The directory is only created
+/// once the first photo is recorded.
+pub struct Journal {
+    journal_dir: PathBuf,
+    mode: BackupMode,
+    run: Option<Run>,
+    file_copies: usize,
+}
+
+/// The directory and journal file of a run that has recorded something
+struct Run {
+    id: String,
+    dir: PathBuf,
+    file: File,
+}
+
+impl Run {
+    /// Create a new run directory named after the current time, adding a
+    /// counter when another run already took that name
+    fn create(journal_dir: &Path) -> Result<Self, Box<dyn Error>> {
+        fs::create_dir_all(journal_dir)?;
+
+        let timestamp = Utc::now().format("%Y%m%dT%H%M%S%.3fZ").to_string();
+        let mut attempt = 0;
+        let (id, dir) = loop {
+            let id = match attempt {
+                0 => timestamp.clone(),
+                n => format!("{}-{}", timestamp, n),
+            };
+            let dir = journal_dir.join(&id);
+
+            match fs::create_dir(&dir) {
+                Ok(()) => break (id, dir),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => attempt += 1,
+                Err(e) => {
+                    return Err(format!("Failed to create journal {}: {}", dir.display(), e).into());
+                }
+            }
+        };
+
+        let file = OpenOptions::new()
+            .create_new(true)
+            .append(true)
+            .open(dir.join(JOURNAL_FILE))?;
+
+        Ok(Self { id, dir, file })
+    }
+}
+
+impl Journal {
+    pub fn new(journal_dir: &Path, mode: BackupMode) -> Self {
+        Self {
+            journal_dir: journal_dir.to_path_buf(),
+            mode,
+            run: None,
+            file_copies: 0,
+        }
+    }
+
+    /// ID of the run, once a photo has been recorded
+    pub fn run_id(&self) -> Option<&str> {
+        self.run.as_ref().map(|run| run.id.as_str())
+    }
+
+    /// Save what is needed to restore `photo_path` to the state described by
+    /// `metadata`. Must be called before the photo is written.
+    pub fn record(&mut self, photo_path: &Path, metadata: &Metadata) -> Result<(), Box<dyn Error>> {
+        let photo = fs::canonicalize(photo_path)?;
+
+        let run = match &mut self.run {
+            Some(run) => run,
+            None => self.run.insert(Run::create(&self.journal_dir)?),
+        };
+
+        let backup = match self.mode {
+            BackupMode::Gps => Backup::Gps {
+                tags: Box::new(GpsTags::read(metadata)),
+            },
+            BackupMode::File => {
+                let file_name = photo.file_name().unwrap_or_default().to_string_lossy();
+                let copy =
+                    PathBuf::from(FILES_DIR).join(format!("{:06}-{}", self.file_copies, file_name));
+                fs::create_dir_all(run.dir.join(FILES_DIR))?;
+                fs::copy(&photo, run.dir.join(&copy))?;
+                self.file_copies += 1;
+                Backup::File { copy }
+            }
+        };
+
+        let entry = JournalEntry { photo, backup };
+        writeln!(run.file, "{}", serde_json::to_string(&entry)?)?;
+        run.file.sync_data()?;
+
+        Ok(())
+    }
+}
+
+/// Runs recorded in `journal_dir`, oldest first
+pub fn list_runs(journal_dir: &Path) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut runs = fs::read_dir(journal_dir)?
+        .filter_map(Result::ok)
+        .filter(|entry| entry.path().join(JOURNAL_FILE).is_file())
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .collect::<Vec<_>>();
+    runs.sort();
+    Ok(runs)
+}
+
+/// A restored photo and whether restoring it succeeded
+pub type RestoreResult = (PathBuf, Result<(), String>);
+
+/// Restore every photo touched by `run_id`, newest change first. Each photo
+/// is restored independently; the result lists the outcome per photo.
+pub fn undo(journal_dir: &Path, run_id: &str) -> Result<Vec<RestoreResult>, Box<dyn Error>> {
+    let run_dir = journal_dir.join(run_id);
+    let file = File::open(run_dir.join(JOURNAL_FILE))
+        .map_err(|e| format!("Failed to open journal for run {}: {}", run_id, e))?;
+
+    let mut entries = Vec::new();
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        entries.push(serde_json::from_str::<JournalEntry>(&line)?);
+    }
+
+    let results = entries
+        .into_iter()
+        .rev()
+        .map(|entry| {
+            let result = restore(&run_dir, &entry);
+            (entry.photo, result)
+        })
+        .collect();
+
+    Ok(results)
+}
+
+fn restore(run_dir: &Path, entry: &JournalEntry) -> Result<(), String> {
+    match &entry.backup {
+        Backup::Gps { tags } => {
+            let mut metadata = Metadata::new_from_path(&entry.photo)
+                .map_err(|e| format!("Failed to read metadata: {}", e))?;
+            tags.restore(&mut metadata);
+            metadata
+                .write_to_file(&entry.photo)
+                .map_err(|e| format!("Failed to write metadata: {}", e))
+        }
+        Backup::File { copy } => fs::copy(run_dir.join(copy), &entry.photo)
+            .map(|_| ())
+            .map_err(|e| format!("Failed to copy backup: {}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entry_format() {
+        let entry = JournalEntry {
+            photo: PathBuf::from("/photos/a.jpg"),
+            backup: Backup::File {
+                copy: PathBuf::from("files/000000-a.jpg"),
+            },
+        };
+        let json = serde_json::to_string(&entry).unwrap();
+        assert_eq!(
+            json,
+            r#"{"photo":"/photos/a.jpg","kind":"file","copy":"files/000000-a.jpg"}"#
+        );
+
+        let entry = serde_json::from_str::<JournalEntry>(
+            r#"{"photo":"/photos/b.jpg","kind":"gps","tags":{"latitude_ref":"N"}}"#,
+        )
+        .unwrap();
+        let Backup::Gps { tags } = entry.backup else {
+            panic!("Expected a GPS backup");
+        };
+        assert_eq!(tags.latitude_ref.as_deref(), Some("N"));
+        assert_eq!(tags.latitude, None);
+    }
+
+    #[test]
+    fn test_runs_are_created_on_first_record() {
+        let journal_dir =
+            std::env::temp_dir().join(format!("timeline_geotagger_journal_{}", std::process::id()));
+        let photo = journal_dir.with_extension("jpg");
+        fs::write(&photo, b"photo").unwrap();
+
+        let mut first = Journal::new(&journal_dir, BackupMode::File);
+        let mut second = Journal::new(&journal_dir, BackupMode::File);
+        assert_eq!(first.run_id(), None);
+        assert!(!journal_dir.exists());
+
+        first.record(&photo, &Metadata::new()).unwrap();
+        second.record(&photo, &Metadata::new()).unwrap();
+
+        let runs = list_runs(&journal_dir).unwrap();
+        fs::remove_dir_all(&journal_dir).unwrap();
+        fs::remove_file(&photo).unwrap();
+
+        assert_eq!(runs.len(), 2);
+        assert_ne!(first.run_id(), second.run_id());
+        assert!(runs.iter().any(|run| Some(run.as_str()) == first.run_id()));
+        assert!(runs.iter().any(|run| Some(run.as_str()) == second.run_id()));
+    }
+}
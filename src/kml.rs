@@ -0,0 +1,267 @@
+use chrono::{DateTime, Utc};
+use quick_xml::{Reader, events::Event};
+use std::{error::Error, fs::File, io::Read, path::Path};
+
+use crate::{
+    line::PointSource,
+    track::{TrackPoint, spread_by_distance},
+};
+
+/// Times and geometry of the `Placemark` being read
+#[derive(Default)]
+struct Placemark {
+    when: Option<DateTime<Utc>>,
+    begin: Option<DateTime<Utc>>,
+    end: Option<DateTime<Utc>>,
+    point: Option<(f64, f64, Option<f64>)>,
+    line: Vec<(f64, f64, Option<f64>)>,
+}
+
+impl Placemark {
+    fn into_track_points(self) -> Vec<TrackPoint> {
+        if !self.line.is_empty()
+            && let (Some(begin), Some(end)) = (self.begin, self.end)
+        {
+            let route = self
+                .line
+                .iter()
+                .map(|(lat, lng, _)| (*lat, *lng))
+                .collect::<Vec<_>>();
+
+            return self
+                .line
+                .iter()
+                .zip(spread_by_distance(&route, begin, end))
+                .map(|(coordinate, timestamp)| track_point(*coordinate, timestamp))
+                .collect();
+        }
+
+        match (self.point, self.when.or(self.begin)) {
+            (Some(coordinate), Some(timestamp)) => vec![track_point(coordinate, timestamp)],
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// `gx:Track` being read; its `when` and `gx:coord` elements pair up in order
+#[derive(Default)]
+struct Track {
+    whens: Vec<Option<DateTime<Utc>>>,
+    coords: Vec<Option<(f64, f64, Option<f64>)>>,
+}
+
+fn track_point(
+    (lat, lng, altitude): (f64, f64, Option<f64>),
+    timestamp: DateTime<Utc>,
+) -> TrackPoint {
+    TrackPoint {
+        lat,
+        lng,
+        altitude,
+        accuracy: None,
+        speed: None,
+        timestamp,
+        source: PointSource::Kml,
+    }
+}
+
+/// Strip any namespace prefix, e.g. `gx:coord`
+fn local_name(name: &str) -> &str {
+    name.rsplit_once(':').map_or(name, |(_, local)| local)
+}
+
+fn parse_time(text: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(text.trim())
+        .ok()
+        .map(|timestamp| timestamp.to_utc())
+}
+
+/// Parse a `gx:coord`: longitude, latitude and optional altitude separated by
+/// spaces
+fn parse_gx_coord(text: &str) -> Option<(f64, f64, Option<f64>)> {
+    parse_coordinate(&text.split_whitespace().collect::<Vec<_>>())
+}
+
+/// Parse the tuples of `<coordinates>`: `lng,lat[,alt]` separated by spaces
+fn parse_coordinates(text: &str) -> Vec<(f64, f64, Option<f64>)> {
+    text.split_whitespace()
+        .filter_map(|tuple| parse_coordinate(&tuple.split(',').collect::<Vec<_>>()))
+        .collect()
+}
+
+fn parse_coordinate(parts: &[&str]) -> Option<(f64, f64, Option<f64>)> {
+    let (lng, lat) = (parts.first()?.parse().ok()?, parts.get(1)?.parse().ok()?);
+    let altitude = parts.get(2).and_then(|altitude| altitude.parse().ok());
+    Some((lat, lng, altitude))
+}
+
+/// Parse the timestamped positions of a KML document: `gx:Track` elements,
+/// and `LineString` or `Point` placemarks with a `TimeSpan` or `TimeStamp`.
+/// `LineString` positions are spread over the time span by distance.
+pub fn parse(xml: &str) -> Result<Vec<TrackPoint>, Box<dyn Error>> {
+    let mut reader = Reader::from_str(xml);
+    let mut points = Vec::new();
+    let mut stack: Vec<String> = Vec::new();
+    let mut placemark: Option<Placemark> = None;
+    let mut track: Option<Track> = None;
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(element) => {
+                let name = local_name(element.name().as_ref()).to_string();
+                match name.as_str() {
+                    "Placemark" => placemark = Some(Placemark::default()),
+                    "Track" => track = Some(Track::default()),
+                    _ => {}
+                }
+                stack.push(name);
+            }
+            Event::Text(text) => {
+                let text = text.xml10_content();
+                let (Some(name), Some(parent)) = (
+                    stack.last().map(String::as_str),
+                    stack.iter().rev().nth(1).map(String::as_str),
+                ) else {
+                    continue;
+                };
+
+                if parent == "Track"
+                    && let Some(track) = track.as_mut()
+                {
+                    match name {
+                        "when" => track.whens.push(parse_time(&text)),
+                        "coord" => track.coords.push(parse_gx_coord(&text)),
+                        _ => {}
+                    }
+                } else if let Some(placemark) = placemark.as_mut() {
+                    match (parent, name) {
+                        ("TimeStamp", "when") => placemark.when = parse_time(&text),
+                        ("TimeSpan", "begin") => placemark.begin = parse_time(&text),
+                        ("TimeSpan", "end") => placemark.end = parse_time(&text),
+                        ("Point", "coordinates") => {
+                            placemark.point = parse_coordinates(&text).into_iter().next();
+                        }
+                        ("LineString", "coordinates") => placemark.line = parse_coordinates(&text),
+                        _ => {}
+                    }
+                }
+            }
+            Event::End(element) => {
+                match local_name(element.name().as_ref()) {
+                    "Track" => {
+                        if let Some(track) = track.take() {
+                            points.extend(
+                                track
+                                    .whens
+                                    .into_iter()
+                                    .zip(track.coords)
+                                    .filter_map(|(when, coord)| Some(track_point(coord?, when?))),
+                            );
+                        }
+                    }
+                    "Placemark" => {
+                        if let Some(placemark) = placemark.take() {
+                            points.extend(placemark.into_track_points());
+                        }
+                    }
+                    _ => {}
+                }
+                stack.pop();
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(points)
+}
+
+/// Parse a KMZ archive: the first `.kml` document in it, preferring the
+/// conventional `doc.kml`
+pub fn parse_kmz(path: &Path) -> Result<Vec<TrackPoint>, Box<dyn Error>> {
+    let mut archive = zip::ZipArchive::new(File::open(path)?)?;
+
+    let name = archive
+        .file_names()
+        .filter(|name| name.to_ascii_lowercase().ends_with(".kml"))
+        .min_by_key(|name| *name != "doc.kml")
+        .map(str::to_string)
+        .ok_or("KMZ archive contains no KML document")?;
+
+    let mut xml = String::new();
+    archive.by_name(&name)?.read_to_string(&mut xml)?;
+
+    parse(&xml)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let xml = std::fs::read_to_string("tests/track_example.kml").unwrap();
+        let points = parse(&xml).unwrap();
+
+        // gx:Track, skipping the pair with an invalid time
+        assert_eq!(points[0].lat, 48.8583701);
+        assert_eq!(points[0].lng, 2.2944813);
+        assert_eq!(points[0].altitude, Some(35.0));
+        assert_eq!(
+            points[0].timestamp.to_rfc3339(),
+            "2024-05-04T08:00:00+00:00"
+        );
+        assert_eq!(
+            points[1].timestamp.to_rfc3339(),
+            "2024-05-04T08:02:00+00:00"
+        );
+
+        // LineString spread over its time span; both legs are equally long
+        assert_eq!(
+            points[2].timestamp.to_rfc3339(),
+            "2024-05-04T09:00:00+00:00"
+        );
+        assert_eq!((points[3].lat, points[3].lng), (51.0, 0.5));
+        assert_eq!(
+            points[3].timestamp.to_rfc3339(),
+            "2024-05-04T09:30:00+00:00"
+        );
+        assert_eq!(
+            points[4].timestamp.to_rfc3339(),
+            "2024-05-04T10:00:00+00:00"
+        );
+
+        // Point with a TimeStamp
+        assert_eq!(points[5].altitude, None);
+        assert_eq!(
+            points[5].timestamp.to_rfc3339(),
+            "2024-05-04T11:00:00+00:00"
+        );
+
+        assert_eq!(points.len(), 6);
+        assert!(points.iter().all(|point| point.source == PointSource::Kml));
+    }
+
+    #[test]
+    fn test_parse_kmz() {
+        let path =
+            std::env::temp_dir().join(format!("timeline_geotagger_kmz_{}.kmz", std::process::id()));
+
+        let mut writer = zip::ZipWriter::new(File::create(&path).unwrap());
+        let options = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Stored);
+        writer.start_file("files/readme.txt", options).unwrap();
+        writer.start_file("doc.kml", options).unwrap();
+        std::io::Write::write_all(
+            &mut writer,
+            std::fs::read("tests/track_example.kml").unwrap().as_slice(),
+        )
+        .unwrap();
+        writer.finish().unwrap();
+
+        let points = parse_kmz(&path).unwrap();
+        assert_eq!(points.len(), 6);
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
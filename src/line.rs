@@ -1,48 +1,88 @@
 use chrono::{DateTime, Utc};
+use std::fmt;
+
+/// Mean Earth radius in meters
+const EARTH_RADIUS_METERS: f64 = 6_371_008.8;
+
+/// Walking pace, how fast a position is assumed to drift away from the last
+/// known fix
+const DRIFT_METERS_PER_SECOND: f64 = 1.4;
+
+/// Great-circle distance in meters between two `(lat, lng)` positions
+pub fn great_circle_distance(from: (f64, f64), to: (f64, f64)) -> f64 {
+    let (lat1, lng1) = (from.0.to_radians(), from.1.to_radians());
+    let (lat2, lng2) = (to.0.to_radians(), to.1.to_radians());
+
+    let a = ((lat2 - lat1) / 2.0).sin().powi(2)
+        + lat1.cos() * lat2.cos() * ((lng2 - lng1) / 2.0).sin().powi(2);
+
+    2.0 * EARTH_RADIUS_METERS * a.sqrt().min(1.0).asin()
+}
+
+/// Which part of the location data a point was taken from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PointSource {
+    RawSignal,
+    SemanticPath,
+    Gpx,
+    Kml,
+    Nmea,
+    Fit,
+    Csv,
+    GeoJson,
+}
+
+impl fmt::Display for PointSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PointSource::RawSignal => write!(f, "raw signal"),
+            PointSource::SemanticPath => write!(f, "semantic path"),
+            PointSource::Gpx => write!(f, "GPX track"),
+            PointSource::Kml => write!(f, "KML track"),
+            PointSource::Nmea => write!(f, "NMEA log"),
+            PointSource::Fit => write!(f, "FIT activity"),
+            PointSource::Csv => write!(f, "CSV log"),
+            PointSource::GeoJson => write!(f, "GeoJSON"),
+        }
+    }
+}
+
+impl PointSource {
+    /// Typical horizontal error in meters of fixes from this source, for
+    /// those that don't record their own
+    pub fn default_accuracy(&self) -> f64 {
+        match self {
+            PointSource::RawSignal => 30.0,
+            PointSource::SemanticPath => 100.0,
+            PointSource::Gpx | PointSource::Kml | PointSource::Nmea | PointSource::Fit => 10.0,
+            PointSource::Csv | PointSource::GeoJson => 20.0,
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct Point {
     pub lat: f64,
     pub lng: f64,
     pub altitude: Option<f64>,
+    /// Estimated horizontal error in meters, when the source records one
+    pub accuracy: Option<f64>,
+    /// Estimated horizontal error in meters of a position found on a line,
+    /// allowing for the time between it and the nearest fix
+    pub estimated_error: Option<f64>,
     pub timestamp: DateTime<Utc>,
     pub relative_seconds: i64,
+    pub source: PointSource,
 }
 
 impl Point {
-    pub fn from_timeline(
-        lat_lng: &str,
-        point_timestamp: &String,
-        altitude: &Option<f64>,
-        relative_timestamp: &DateTime<Utc>,
-    ) -> Result<Self, String> {
-        let timestamp = match DateTime::parse_from_rfc3339(point_timestamp.as_str()) {
-            Ok(dt) => dt.with_timezone(&Utc),
-            Err(_) => {
-                return Err(format!("Invalid timestamp format: {}", point_timestamp).into());
-            }
-        };
-
-        let relative_seconds = (timestamp - *relative_timestamp).num_seconds();
-
-        let (lat, lng) = match Point::parse_lat_lng(&lat_lng) {
-            Some((lat, lng)) => (lat, lng),
-            None => {
-                return Err(format!("Invalid latitude/longitude format: {}", lat_lng).into());
-            }
-        };
-
-        Ok(Self {
-            lat,
-            lng,
-            altitude: altitude.clone(),
-            timestamp,
-            relative_seconds,
-        })
-    }
-
+    /// Parse `"54.7973628°, -1.5921431°"` or `"geo:54.7973628,-1.5921431"`
     pub fn parse_lat_lng(lat_lng: &str) -> Option<(f64, f64)> {
-        let trimmed = lat_lng.trim().replace("°", "");
+        let trimmed = lat_lng.trim();
+        let trimmed = trimmed
+            .strip_prefix("geo:")
+            .unwrap_or(trimmed)
+            .replace("°", "");
         let parts: Vec<&str> = trimmed.split(',').collect();
         if parts.len() == 2 {
             let lat = parts[0].trim().parse::<f64>().ok()?;
@@ -54,6 +94,64 @@ impl Point {
     }
 }
 
+/// Unit vector on the sphere for a `(lat, lng)` position
+fn to_unit_vector((lat, lng): (f64, f64)) -> [f64; 3] {
+    let (lat, lng) = (lat.to_radians(), lng.to_radians());
+    [lat.cos() * lng.cos(), lat.cos() * lng.sin(), lat.sin()]
+}
+
+fn from_unit_vector([x, y, z]: [f64; 3]) -> (f64, f64) {
+    let lat = z.atan2((x * x + y * y).sqrt()).to_degrees();
+    let lng = y.atan2(x).to_degrees();
+    (lat, lng)
+}
+
+/// Position a fraction `progress` of the way along the shortest great circle
+/// from `from` to `to`. Working with unit vectors (spherical linear
+/// interpolation) rather than degrees keeps the path correct near the poles
+/// and across the ±180° meridian.
+pub fn interpolate_great_circle(
+    from: (f64, f64),
+    to: (f64, f64),
+    progress: f64,
+) -> Result<(f64, f64), String> {
+    let a = to_unit_vector(from);
+    let b = to_unit_vector(to);
+
+    let dot = (a[0] * b[0] + a[1] * b[1] + a[2] * b[2]).clamp(-1.0, 1.0);
+    let omega = dot.acos();
+
+    if omega < 1e-12 {
+        return Ok(from);
+    }
+
+    let sin_omega = omega.sin();
+    if sin_omega < 1e-12 {
+        return Err("Points are antipodal, the path between them is undefined".into());
+    }
+
+    let weight_a = ((1.0 - progress) * omega).sin() / sin_omega;
+    let weight_b = (progress * omega).sin() / sin_omega;
+
+    Ok(from_unit_vector([
+        weight_a * a[0] + weight_b * b[0],
+        weight_a * a[1] + weight_b * b[1],
+        weight_a * a[2] + weight_b * b[2],
+    ]))
+}
+
+/// Limits on how far apart in time the points around a timestamp may be
+/// before interpolating between them is considered unreliable
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MaxGap {
+    /// Seconds between the start and end point
+    pub total: Option<i64>,
+    /// Seconds between the start point and the timestamp
+    pub before: Option<i64>,
+    /// Seconds between the timestamp and the end point
+    pub after: Option<i64>,
+}
+
 pub struct Line {
     pub start: Point,
     pub end: Point,
@@ -64,92 +162,204 @@ impl Line {
         Line { start, end }
     }
 
+    /// Seconds between the start point and the requested timestamp
+    pub fn gap_before(&self) -> i64 {
+        -self.start.relative_seconds
+    }
+
+    /// Seconds between the requested timestamp and the end point
+    pub fn gap_after(&self) -> i64 {
+        self.end.relative_seconds
+    }
+
+    /// Rough horizontal error in meters of a position interpolated on this
+    /// line: the accuracy of its less accurate end, plus how far someone
+    /// walking could get from the nearer fix
+    pub fn estimated_error(&self) -> f64 {
+        let accuracy = |point: &Point| {
+            point
+                .accuracy
+                .unwrap_or_else(|| point.source.default_accuracy())
+        };
+        let nearest_gap = self.gap_before().min(self.gap_after()) as f64;
+
+        accuracy(&self.start).max(accuracy(&self.end)) + nearest_gap * DRIFT_METERS_PER_SECOND
+    }
+
+    /// Check the line against `max_gap`, describing the first limit exceeded
+    pub fn check_gap(&self, max_gap: &MaxGap) -> Result<(), String> {
+        let total = self.gap_before() + self.gap_after();
+
+        if let Some(max) = max_gap.total
+            && total > max
+        {
+            return Err(format!("nearest fixes are {}s apart (max {}s)", total, max));
+        }
+
+        if let Some(max) = max_gap.before
+            && self.gap_before() > max
+        {
+            return Err(format!(
+                "nearest fix before is {}s earlier (max {}s)",
+                self.gap_before(),
+                max
+            ));
+        }
+
+        if let Some(max) = max_gap.after
+            && self.gap_after() > max
+        {
+            return Err(format!(
+                "nearest fix after is {}s later (max {}s)",
+                self.gap_after(),
+                max
+            ));
+        }
+
+        Ok(())
+    }
+
     pub fn get_point_at(&self, timestamp: &DateTime<Utc>) -> Result<Point, String> {
-        if self.start.relative_seconds == self.end.relative_seconds {
-            return Ok(self.start.clone());
+        if self.start.timestamp == self.end.timestamp {
+            return Ok(Point {
+                estimated_error: Some(self.estimated_error()),
+                ..self.start.clone()
+            });
         }
 
         if timestamp < &self.start.timestamp || timestamp > &self.end.timestamp {
             return Err("Timestamp is out of bounds".into());
         }
 
-        let total_duration = self.end.relative_seconds - self.start.relative_seconds;
-        let elapsed_duration = -self.start.relative_seconds;
-        let progress: f64 = elapsed_duration as f64 / total_duration as f64;
-
-        let lat = self.start.lat + (self.end.lat - self.start.lat) * progress;
-        let lng = self.start.lng + (self.end.lng - self.start.lng) * progress;
-        let altitude = if self.start.altitude.is_some() && self.end.altitude.is_some() {
-            Some(
-                self.start.altitude.unwrap()
-                    + (self.end.altitude.unwrap() - self.start.altitude.unwrap()) * progress,
-            )
-        } else {
-            None
+        // Relative seconds are truncated, so use the exact times for photos
+        // and fixes with sub-second precision
+        let total_duration = self.end.timestamp - self.start.timestamp;
+        let elapsed_duration = *timestamp - self.start.timestamp;
+        let progress =
+            elapsed_duration.num_milliseconds() as f64 / total_duration.num_milliseconds() as f64;
+
+        let (lat, lng) = interpolate_great_circle(
+            (self.start.lat, self.start.lng),
+            (self.end.lat, self.end.lng),
+            progress,
+        )?;
+        let altitude = match (self.start.altitude, self.end.altitude) {
+            (Some(start), Some(end)) => Some(start + (end - start) * progress),
+            _ => None,
+        };
+        let accuracy = match (self.start.accuracy, self.end.accuracy) {
+            (Some(start), Some(end)) => Some(start + (end - start) * progress),
+            _ => None,
         };
 
         Ok(Point {
             lat,
             lng,
             altitude,
-            timestamp: timestamp.clone(),
+            accuracy,
+            estimated_error: Some(self.estimated_error()),
+            timestamp: *timestamp,
             relative_seconds: 0,
+            source: self.start.source,
         })
     }
 }
 
-pub struct LineBuilder {
-    start: Option<Point>,
-    end: Option<Point>,
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeDelta;
 
-impl LineBuilder {
-    pub fn new() -> Self {
-        LineBuilder {
-            start: None,
-            end: None,
-        }
-    }
+    #[test]
+    fn test_interpolate_across_antimeridian() {
+        // Suva, Fiji to Apia, Samoa crosses ±180° instead of circling the globe
+        let (lat, lng) =
+            interpolate_great_circle((-18.1416, 178.4419), (-13.8333, -171.75), 0.5).unwrap();
+        assert!((lat - -16.043167032067604).abs() < 1e-9);
+        assert!((lng - -176.6010699435853).abs() < 1e-9);
 
-    pub fn start(&mut self, point: Point) -> &mut Self {
-        self.start = Some(point);
-        self
+        let (_, lng) =
+            interpolate_great_circle((-18.1416, 178.4419), (-13.8333, -171.75), 0.1).unwrap();
+        assert!(lng > 178.4419 && lng <= 180.0);
     }
 
-    pub fn end(&mut self, point: Point) -> &mut Self {
-        self.end = Some(point);
-        self
+    #[test]
+    fn test_interpolate_over_pole() {
+        // Halfway between opposite meridians at 80°N is the North Pole
+        let (lat, _) = interpolate_great_circle((80.0, 0.0), (80.0, 180.0), 0.5).unwrap();
+        assert!((lat - 90.0).abs() < 1e-9);
+
+        // A quarter of the way stays on the starting meridian
+        let (lat, lng) = interpolate_great_circle((80.0, -10.0), (80.0, 170.0), 0.25).unwrap();
+        assert!((lat - 85.0).abs() < 1e-9);
+        assert!((lng - -10.0).abs() < 1e-9);
     }
 
-    pub fn add_point(&mut self, point: Point) -> &mut Self {
-        if point.relative_seconds < 0
-            && (self.start.is_none()
-                || self.start.as_ref().unwrap().relative_seconds < point.relative_seconds)
-        {
-            return self.start(point);
-        }
+    #[test]
+    fn test_interpolate_endpoints() {
+        let (lat, lng) = interpolate_great_circle((10.0, 20.0), (11.0, 21.0), 0.0).unwrap();
+        assert!((lat - 10.0).abs() < 1e-9 && (lng - 20.0).abs() < 1e-9);
 
-        if point.relative_seconds > 0
-            && (self.end.is_none()
-                || self.end.as_ref().unwrap().relative_seconds > point.relative_seconds)
-        {
-            return self.end(point);
-        }
+        let (lat, lng) = interpolate_great_circle((10.0, 20.0), (11.0, 21.0), 1.0).unwrap();
+        assert!((lat - 11.0).abs() < 1e-9 && (lng - 21.0).abs() < 1e-9);
 
-        self
+        assert_eq!(
+            interpolate_great_circle((10.0, 20.0), (10.0, 20.0), 0.5),
+            Ok((10.0, 20.0))
+        );
+        assert!(interpolate_great_circle((0.0, 0.0), (0.0, 180.0), 0.5).is_err());
     }
 
-    pub fn build(self) -> Option<Line> {
-        match (self.start, self.end) {
-            (Some(start), Some(end)) => Some(Line::new(start, end)),
-            _ => None,
-        }
+    #[test]
+    fn test_get_point_at_fractional_seconds() {
+        let photo_time = DateTime::parse_from_rfc3339("2024-01-01T12:00:00.500Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let point = |milliseconds: i64, altitude: f64| {
+            let timestamp = photo_time + TimeDelta::milliseconds(milliseconds);
+            Point {
+                lat: 10.0,
+                lng: 20.0,
+                altitude: Some(altitude),
+                accuracy: None,
+                estimated_error: None,
+                timestamp,
+                relative_seconds: (timestamp - photo_time).num_seconds(),
+                source: PointSource::RawSignal,
+            }
+        };
+
+        // Fixes 2.5s before and 1.5s after the photo: 0.625 of the way along
+        let line = Line::new(point(-2500, 0.0), point(1500, 8.0));
+        let located = line.get_point_at(&photo_time).unwrap();
+        assert!((located.altitude.unwrap() - 5.0).abs() < 1e-9);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn test_parse_lat_lng() {
+        assert_eq!(
+            Point::parse_lat_lng("54.7973628°, -1.5921431°"),
+            Some((54.7973628, -1.5921431))
+        );
+        assert_eq!(
+            Point::parse_lat_lng("geo:54.797363,-1.592143"),
+            Some((54.797363, -1.592143))
+        );
+        assert_eq!(Point::parse_lat_lng("geo:54.797363"), None);
+    }
+
+    #[test]
+    fn test_great_circle_distance() {
+        assert_eq!(great_circle_distance((51.5, -0.1), (51.5, -0.1)), 0.0);
+
+        // One degree of latitude is roughly 111 km
+        let distance = great_circle_distance((50.0, 5.0), (51.0, 5.0));
+        assert!((distance - 111_195.0).abs() < 10.0);
+
+        // Across the antimeridian is a short hop, not half the planet
+        let distance = great_circle_distance((-17.0, 179.9), (-17.0, -179.9));
+        assert!(distance < 25_000.0);
+    }
 
     #[test]
     fn test_get_point_at() {
@@ -158,19 +368,25 @@ mod tests {
                 lat: 55.0000000,
                 lng: -1.5000000,
                 altitude: Some(75.0000000000000),
+                accuracy: None,
+                estimated_error: None,
                 timestamp: DateTime::parse_from_rfc3339("2025-07-11T16:20:00.000+01:00")
                     .unwrap()
                     .with_timezone(&Utc),
                 relative_seconds: -60,
+                source: PointSource::RawSignal,
             },
             Point {
                 lat: 57.0000000,
                 lng: -2.0000000,
                 altitude: Some(76.0000000000000),
+                accuracy: None,
+                estimated_error: None,
                 timestamp: DateTime::parse_from_rfc3339("2025-07-11T16:25:00.000+01:00")
                     .unwrap()
                     .with_timezone(&Utc),
                 relative_seconds: 240,
+                source: PointSource::RawSignal,
             },
         );
 
@@ -178,13 +394,54 @@ mod tests {
             .unwrap()
             .with_timezone(&Utc);
 
+        assert_eq!(line.gap_before(), 60);
+        assert_eq!(line.gap_after(), 240);
+
+        // Default raw signal accuracy, plus a minute's walk from the fix before
+        assert!((line.estimated_error() - 114.0).abs() < 1e-9);
+
+        assert!(line.check_gap(&MaxGap::default()).is_ok());
+        assert!(
+            line.check_gap(&MaxGap {
+                total: Some(300),
+                before: Some(60),
+                after: Some(240),
+            })
+            .is_ok()
+        );
+        assert!(
+            line.check_gap(&MaxGap {
+                total: Some(299),
+                ..Default::default()
+            })
+            .is_err()
+        );
+        assert!(
+            line.check_gap(&MaxGap {
+                before: Some(59),
+                ..Default::default()
+            })
+            .is_err()
+        );
+        assert!(
+            line.check_gap(&MaxGap {
+                after: Some(239),
+                ..Default::default()
+            })
+            .is_err()
+        );
+
         let location = line.get_point_at(&timestamp);
 
         assert!(location.is_ok());
         let location = location.unwrap();
-        assert_eq!(location.lat, 55.4000000);
-        assert_eq!(location.lng, -1.6000000);
+        // Slightly north of the straight 55.4, -1.6 in degrees, as great
+        // circles bulge towards the pole
+        assert!((location.lat - 55.40015887423456).abs() < 1e-9);
+        assert!((location.lng - -1.595931819078289).abs() < 1e-9);
         assert_eq!(location.altitude, Some(75.2000000000000));
         assert_eq!(location.relative_seconds, 0);
+        assert_eq!(location.source, PointSource::RawSignal);
+        assert_eq!(location.estimated_error, Some(line.estimated_error()));
     }
 }
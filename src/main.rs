@@ -1,82 +1,428 @@
+pub mod cli;
+pub mod clock;
+pub mod csv_track;
 pub mod file_system_autocomplete;
+pub mod fit;
+pub mod geojson;
 pub mod geotag;
+pub mod gps_tags;
+pub mod gpx;
+pub mod journal;
+pub mod kml;
 pub mod line;
+pub mod mobile_export;
+pub mod nmea;
+pub mod photo_finder;
+pub mod point_index;
+pub mod records;
+pub mod semantic_history;
 pub mod timeline;
+pub mod timeline_json;
 pub mod timezone_autocomplete;
+pub mod track;
+pub mod xmp;
 
 use crate::{
-    file_system_autocomplete::FileSystemAutocomplete, timeline::Timeline,
+    cli::{Cli, Command},
+    clock::{ClockOffsets, Reference},
+    csv_track::CsvFormat,
+    file_system_autocomplete::FileSystemAutocomplete,
+    geotag::GeotagOptions,
+    journal::Journal,
+    line::MaxGap,
+    photo_finder::PhotoFinder,
+    timeline::{SourceOptions, TimeWindow, Timeline},
     timezone_autocomplete::TimezoneAutocomplete,
 };
+use chrono::{DateTime, TimeDelta, Utc};
 use chrono_tz::Tz;
+use clap::Parser;
 use inquire::{Text, validator::Validation};
-use std::{path::Path, str::FromStr};
+use little_exif::metadata::Metadata;
+use std::{
+    io::{IsTerminal, stdin},
+    path::{Path, PathBuf},
+    process::ExitCode,
+    str::FromStr,
+};
 
-fn main() {
-    println!("----- GOOGLE MAPS GEOTAGGER -----");
+/// Exit code used when the batch ran but some photos could not be geotagged
+const PARTIAL_FAILURE_EXIT_CODE: u8 = 2;
 
-    let timeline_path = Text::new("Path to Timeline.json file:")
-        .with_autocomplete(FileSystemAutocomplete::files())
-        .with_validator(|input: &str| {
-            if input.is_empty() {
-                Ok(Validation::Valid)
-            } else if Path::new(input).exists() && Path::new(input).is_file() {
-                Ok(Validation::Valid)
-            } else {
-                Ok(Validation::Invalid("File does not exist".into()))
+fn main() -> ExitCode {
+    let mut cli = Cli::parse();
+
+    match cli.command.take() {
+        Some(Command::Undo { run, journal_dir }) => undo(&journal_dir, run.as_deref()),
+        None => geotag(cli),
+    }
+}
+
+fn geotag(cli: Cli) -> ExitCode {
+    let interactive = !cli.no_input && stdin().is_terminal();
+
+    if interactive {
+        println!("----- GOOGLE MAPS GEOTAGGER -----");
+    }
+
+    let timeline_paths = if !cli.timeline.is_empty() {
+        cli.timeline
+    } else if interactive {
+        match prompt_timeline_path() {
+            Ok(path) => vec![path],
+            Err(e) => return exit_with_error(&e),
+        }
+    } else {
+        return exit_with_error("Missing --timeline (prompting is disabled)");
+    };
+
+    for timeline_path in &timeline_paths {
+        if !timeline_path.exists() {
+            return exit_with_error(&format!(
+                "Timeline file or directory does not exist: {}",
+                timeline_path.display()
+            ));
+        }
+    }
+
+    let mut source_options = SourceOptions {
+        csv: CsvFormat {
+            time_column: cli.csv_time,
+            lat_column: cli.csv_lat,
+            lng_column: cli.csv_lng,
+            altitude_column: cli.csv_altitude,
+            accuracy_column: cli.csv_accuracy,
+            time_format: cli.csv_time_format,
+        },
+        time_window: None,
+        max_accuracy: cli.max_accuracy,
+    };
+    if let Some(unknown) = cli
+        .timeline_priority
+        .iter()
+        .find(|source| !timeline_paths.contains(&source.path))
+    {
+        return exit_with_error(&format!(
+            "--timeline-priority names a path not given with --timeline: {}",
+            unknown.path.display()
+        ));
+    }
+
+    let photos_paths = if !cli.photos.is_empty() {
+        cli.photos
+    } else if interactive {
+        match prompt_photos_path() {
+            Ok(path) => vec![path],
+            Err(e) => return exit_with_error(&e),
+        }
+    } else {
+        return exit_with_error("Missing --photos (prompting is disabled)");
+    };
+
+    let finder = match PhotoFinder::new(&cli.include, &cli.exclude) {
+        Ok(finder) => finder
+            .recursive(cli.recursive)
+            .follow_symlinks(cli.follow_symlinks)
+            .max_depth(cli.max_depth),
+        Err(e) => return exit_with_error(&format!("Invalid glob pattern: {}", e)),
+    };
+
+    let photos = match finder.find(&photos_paths) {
+        Ok(found) => {
+            for error in &found.unreadable {
+                eprintln!("Skipping unreadable path: {}", error);
             }
-        })
-        .prompt()
-        .expect("Failed to read input");
+            found.photos
+        }
+        Err(e) => return exit_with_error(&format!("Error finding photos: {}", e)),
+    };
+
+    println!("Found {} photos", photos.len());
+
+    if let Some(days) = cli.load_window {
+        let mut photo_paths = photos.clone();
+        photo_paths.extend(cli.reference_photo.clone());
+        let clock_offsets = [cli.clock_offset.unwrap_or_default()]
+            .into_iter()
+            .chain(cli.camera_offset.iter().map(|camera| camera.offset));
+        source_options.time_window = photo_time_window(&photo_paths, days, clock_offsets);
+    }
 
-    let timeline_path = Path::new(&timeline_path);
+    let mut timeline: Option<Timeline> = None;
+
+    for timeline_path in &timeline_paths {
+        println!("Parsing timeline file: {}", timeline_path.display());
+
+        let source = match Timeline::from_path_with(timeline_path, &source_options) {
+            Ok(json) => json,
+            Err(e) => {
+                return exit_with_error(&format!(
+                    "Error parsing timeline file {}: {}",
+                    timeline_path.display(),
+                    e
+                ));
+            }
+        };
+
+        let warnings = source.parse_report().warnings();
+        if !warnings.is_empty() {
+            eprintln!("Ignored in {}:", timeline_path.display());
+            for warning in warnings {
+                eprintln!("  {}", warning);
+            }
+        }
 
-    println!("Parsing timeline file: {}", timeline_path.display());
+        let priority = cli
+            .timeline_priority
+            .iter()
+            .rfind(|source| &source.path == timeline_path)
+            .map_or(0, |source| source.priority);
+        let source = source.with_priority(priority);
 
-    let timeline = match Timeline::from_path(timeline_path) {
-        Ok(json) => json,
-        Err(e) => {
-            eprintln!("Error parsing timeline file: {}", e);
-            return;
+        // Each path is its own source, so tracks from different devices are
+        // never joined into one line
+        match timeline.as_mut() {
+            Some(timeline) => timeline.add_source(source),
+            None => timeline = Some(source),
         }
+    }
+
+    // At least one path was given or prompted for
+    let Some(timeline) = timeline else {
+        return exit_with_error("Missing --timeline");
     };
 
-    let photos_path = Text::new("Path to your photos directory:")
-        .with_autocomplete(FileSystemAutocomplete::directories())
+    let photo_timezone = match cli.timezone {
+        Some(timezone) => timezone,
+        None if interactive => match prompt_timezone() {
+            Ok(timezone) => timezone,
+            Err(e) => return exit_with_error(&e),
+        },
+        None => return exit_with_error("Missing --timezone (prompting is disabled)"),
+    };
+
+    let mut options = GeotagOptions {
+        photo_timezone,
+        auto_timezone: cli.auto_timezone,
+        clock_offsets: ClockOffsets {
+            default: cli.clock_offset.unwrap_or_default(),
+            cameras: cli.camera_offset,
+        },
+        dry_run: cli.dry_run,
+        write_mode: cli.write,
+        existing_gps: cli.existing_gps,
+        far_distance: cli.far_distance,
+        max_gap: MaxGap {
+            total: cli.max_gap,
+            before: cli.max_gap_before,
+            after: cli.max_gap_after,
+        },
+    };
+
+    if let Some(reference_photo) = &cli.reference_photo {
+        let reference = match (cli.reference_time, cli.reference_location) {
+            (Some(time), _) => Reference::ShownTime(time),
+            (None, Some(location)) => Reference::Location(location),
+            (None, None) => {
+                return exit_with_error("Missing --reference-time or --reference-location");
+            }
+        };
+
+        let calibration = match clock::calibrate(&timeline, reference_photo, &reference, &options) {
+            Ok(calibration) => calibration,
+            Err(e) => {
+                return exit_with_error(&format!(
+                    "Error calibrating clock from {}: {}",
+                    reference_photo.display(),
+                    e
+                ));
+            }
+        };
+
+        print!(
+            "Clock offset for {}: {}",
+            calibration.camera,
+            clock::format_clock_offset(calibration.offset)
+        );
+        match calibration.distance {
+            Some(distance) => println!(" (timeline passed {:.0} m from the location)", distance),
+            None => println!(),
+        }
+
+        options
+            .clock_offsets
+            .set(&calibration.camera, calibration.offset);
+    }
+
+    let mut journal = match cli.backup {
+        Some(mode) if !options.dry_run && options.write_mode.writes_exif() => {
+            Some(Journal::new(&cli.journal_dir, mode))
+        }
+        _ => None,
+    };
+
+    let summary = geotag::geotag_photos(&timeline, &photos, &options, journal.as_mut());
+
+    println!();
+    println!("Summary: {}", summary);
+
+    if let Some(run_id) = journal.as_ref().and_then(Journal::run_id) {
+        println!(
+            "Backups saved; undo with: timeline_geotagger undo {} --journal-dir {}",
+            run_id,
+            cli.journal_dir.display()
+        );
+    }
+
+    if summary.has_failures() {
+        eprintln!("The following photos could not be geotagged:");
+        for failure in summary.failures() {
+            eprintln!("  [{}] {}", failure.status, failure.path.display());
+        }
+        return ExitCode::from(PARTIAL_FAILURE_EXIT_CODE);
+    }
+
+    if options.dry_run {
+        println!("Dry run completed, no files were modified.");
+    } else {
+        println!("Geotagging completed successfully!");
+    }
+
+    ExitCode::SUCCESS
+}
+
+fn undo(journal_dir: &Path, run: Option<&str>) -> ExitCode {
+    let Some(run) = run else {
+        return match journal::list_runs(journal_dir) {
+            Ok(runs) if runs.is_empty() => exit_with_error("No runs have been recorded"),
+            Ok(runs) => {
+                println!("Recorded runs:");
+                for run in runs {
+                    println!("  {}", run);
+                }
+                ExitCode::SUCCESS
+            }
+            Err(e) => exit_with_error(&format!("Error reading journal directory: {}", e)),
+        };
+    };
+
+    let results = match journal::undo(journal_dir, run) {
+        Ok(results) => results,
+        Err(e) => return exit_with_error(&format!("Error reading journal: {}", e)),
+    };
+
+    let mut failures = 0;
+    for (photo, result) in &results {
+        match result {
+            Ok(()) => println!("Restored photo: {}", photo.display()),
+            Err(e) => {
+                failures += 1;
+                eprintln!("Failed to restore photo {}: {}", photo.display(), e);
+            }
+        }
+    }
+
+    println!();
+    println!(
+        "Summary: restored: {}, failed: {}",
+        results.len() - failures,
+        failures
+    );
+
+    if failures > 0 {
+        return ExitCode::from(PARTIAL_FAILURE_EXIT_CODE);
+    }
+
+    ExitCode::SUCCESS
+}
+
+/// The times within `days` days of the photos' capture times, allowing for
+/// any timezone and the largest clock offset. `None` if no photo has a time
+/// or the window is too wide to represent.
+fn photo_time_window(
+    photos: &[PathBuf],
+    days: u32,
+    clock_offsets: impl Iterator<Item = TimeDelta>,
+) -> Option<TimeWindow> {
+    let (first, last) = photos
+        .iter()
+        .filter_map(|photo| {
+            let metadata = Metadata::new_from_path(photo).ok()?;
+            geotag::read_photo_time(&metadata).ok()
+        })
+        .map(|(local, _)| local.and_utc())
+        .fold(
+            None,
+            |range: Option<(DateTime<Utc>, DateTime<Utc>)>, time| {
+                Some(match range {
+                    Some((first, last)) => (first.min(time), last.max(time)),
+                    None => (time, time),
+                })
+            },
+        )?;
+
+    // Local times are anywhere from UTC-12 to UTC+14
+    let max_clock_offset = clock_offsets.map(|offset| offset.abs()).max();
+    let padding = TimeDelta::try_days(i64::from(days))?
+        .checked_add(&TimeDelta::hours(14))?
+        .checked_add(&max_clock_offset.unwrap_or_default())?;
+
+    // A window reaching past the dates chrono can represent keeps everything
+    Some(TimeWindow {
+        start: first.checked_sub_signed(padding)?,
+        end: last.checked_add_signed(padding)?,
+    })
+}
+
+fn exit_with_error(message: &str) -> ExitCode {
+    eprintln!("{}", message);
+    ExitCode::FAILURE
+}
+
+fn prompt_timeline_path() -> Result<PathBuf, String> {
+    Text::new("Path to Timeline.json file (or location history directory):")
+        .with_autocomplete(FileSystemAutocomplete::all())
         .with_validator(|input: &str| {
-            if input.is_empty() {
+            if Path::new(input).exists() {
                 Ok(Validation::Valid)
-            } else if Path::new(input).exists() && Path::new(input).is_dir() {
+            } else {
+                Ok(Validation::Invalid(
+                    "File or directory does not exist".into(),
+                ))
+            }
+        })
+        .prompt()
+        .map(PathBuf::from)
+        .map_err(|e| format!("Failed to read input: {}", e))
+}
+
+fn prompt_photos_path() -> Result<PathBuf, String> {
+    Text::new("Path to your photos directory:")
+        .with_autocomplete(FileSystemAutocomplete::directories())
+        .with_validator(|input: &str| {
+            if Path::new(input).is_dir() {
                 Ok(Validation::Valid)
             } else {
                 Ok(Validation::Invalid("Directory does not exist".into()))
             }
         })
         .prompt()
-        .expect("Failed to read input");
-
-    let photos_path = Path::new(&photos_path);
-
-    println!("Using photos directory: {}", photos_path.display());
+        .map(PathBuf::from)
+        .map_err(|e| format!("Failed to read input: {}", e))
+}
 
-    let photo_timezone = Text::new("What timezone were the photos taken in?")
+fn prompt_timezone() -> Result<Tz, String> {
+    let timezone = Text::new("What timezone were the photos taken in?")
         .with_autocomplete(TimezoneAutocomplete)
         .with_validator(|input: &str| {
-            if input.is_empty() {
-                Ok(Validation::Valid)
-            } else if Tz::from_str(input).is_ok() {
+            if Tz::from_str(input).is_ok() {
                 Ok(Validation::Valid)
             } else {
                 Ok(Validation::Invalid("Invalid timezone".into()))
             }
         })
         .prompt()
-        .expect("Failed to read input");
-
-    let photo_timezone = Tz::from_str(&photo_timezone).expect("Failed to parse timezone");
+        .map_err(|e| format!("Failed to read input: {}", e))?;
 
-    match geotag::geotag_photos(&timeline, photos_path, photo_timezone) {
-        Ok(_) => println!("Geotagging completed successfully!"),
-        Err(e) => eprintln!("Error geotagging photos: {}", e),
-    }
+    Tz::from_str(&timezone).map_err(|e| format!("Failed to parse timezone: {}", e))
 }
@@ -0,0 +1,199 @@
+use chrono::{DateTime, FixedOffset, TimeDelta, Utc};
+use serde::Deserialize;
+use std::error::Error;
+
+use crate::{
+    line::Point,
+    timeline::{TimedPosition, UtcOffsetSpan},
+};
+
+#[derive(Deserialize, Debug)]
+struct PlaceCandidate {
+    #[serde(rename = "placeLocation")]
+    place_location: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Visit {
+    #[serde(rename = "topCandidate")]
+    top_candidate: Option<PlaceCandidate>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Activity {
+    #[serde(rename = "start")]
+    start: Option<String>,
+    #[serde(rename = "end")]
+    end: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct PathPoint {
+    #[serde(rename = "point")]
+    point: String,
+    /// Minutes since the entry's start time, as a string
+    #[serde(rename = "durationMinutesOffsetFromStartTime")]
+    duration_minutes_offset_from_start_time: Option<String>,
+}
+
+/// One element of the top-level array exported by the Google Maps app
+#[derive(Deserialize, Debug)]
+struct MobileEntry {
+    #[serde(rename = "startTime")]
+    start_time: String,
+    #[serde(rename = "endTime")]
+    end_time: String,
+    #[serde(rename = "visit")]
+    visit: Option<Visit>,
+    #[serde(rename = "activity")]
+    activity: Option<Activity>,
+    #[serde(rename = "timelinePath")]
+    timeline_path: Option<Vec<PathPoint>>,
+}
+
+impl MobileEntry {
+    fn times(&self) -> Option<(DateTime<FixedOffset>, DateTime<FixedOffset>)> {
+        Some((
+            DateTime::parse_from_rfc3339(&self.start_time).ok()?,
+            DateTime::parse_from_rfc3339(&self.end_time).ok()?,
+        ))
+    }
+
+    /// The UTC offsets the entry's start and end times were recorded with
+    fn utc_offset_span(&self) -> Option<UtcOffsetSpan> {
+        let (start_time, end_time) = self.times()?;
+        Some(UtcOffsetSpan {
+            start: start_time.to_utc(),
+            end: end_time.to_utc(),
+            offset_minutes: [start_time, end_time].map(|time| time.offset().local_minus_utc() / 60),
+        })
+    }
+
+    fn to_path(&self) -> Vec<TimedPosition> {
+        let Some((start_time, end_time)) = self.times() else {
+            return Vec::new();
+        };
+        let (start_time, end_time) = (start_time.to_utc(), end_time.to_utc());
+
+        let mut path = Vec::new();
+        let mut push = |lat_lng: Option<&str>, timestamp: DateTime<Utc>| {
+            if let Some((lat, lng)) = lat_lng.and_then(Point::parse_lat_lng) {
+                path.push((lat, lng, timestamp));
+            }
+        };
+
+        if let Some(visit) = &self.visit {
+            let location = visit
+                .top_candidate
+                .as_ref()
+                .and_then(|candidate| candidate.place_location.as_deref());
+            push(location, start_time);
+            push(location, end_time);
+        }
+
+        if let Some(activity) = &self.activity {
+            push(activity.start.as_deref(), start_time);
+            push(activity.end.as_deref(), end_time);
+        }
+
+        for point in self.timeline_path.iter().flatten() {
+            // Offsets too large for a date are skipped rather than overflowing
+            let Some(timestamp) = point
+                .duration_minutes_offset_from_start_time
+                .as_deref()
+                .and_then(|minutes| minutes.parse::<i64>().ok())
+                .and_then(TimeDelta::try_minutes)
+                .and_then(|offset| start_time.checked_add_signed(offset))
+            else {
+                continue;
+            };
+            push(Some(&point.point), timestamp);
+        }
+
+        path.sort_by_key(|(_, _, timestamp)| *timestamp);
+        path
+    }
+}
+
+/// The timed paths and UTC offsets read from the mobile app export
+#[derive(Debug, Default)]
+pub(crate) struct MobileData {
+    /// One path per visit, activity or timeline path
+    pub paths: Vec<Vec<TimedPosition>>,
+    pub utc_offsets: Vec<UtcOffsetSpan>,
+}
+
+/// Parse the on-device export of the iOS and Android Google Maps app, a
+/// top-level array of visits, activities and timeline paths
+pub(crate) fn parse(bytes: &[u8]) -> Result<MobileData, Box<dyn Error>> {
+    let entries: Vec<MobileEntry> = serde_json::from_slice(bytes)?;
+
+    Ok(MobileData {
+        paths: entries
+            .iter()
+            .map(MobileEntry::to_path)
+            .filter(|path| !path.is_empty())
+            .collect(),
+        utc_offsets: entries
+            .iter()
+            .filter_map(MobileEntry::utc_offset_span)
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{line::PointSource, timeline::Timeline};
+    use chrono::{DateTime, FixedOffset, NaiveDateTime, Utc};
+
+    fn at(timestamp: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(timestamp)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_mobile_export() {
+        let data = Timeline::from_path("tests/mobile_example.json").unwrap();
+
+        // During a visit
+        let line = data.get_line_at(&at("2024-05-04T10:15:00+02:00")).unwrap();
+        assert_eq!(line.start.source, PointSource::SemanticPath);
+        assert_eq!((line.start.lat, line.start.lng), (48.85837, 2.294481));
+        assert_eq!(line.start.relative_seconds, -900);
+        assert_eq!(line.end.relative_seconds, 900);
+
+        // Along a timeline path, between its points 10 and 20 minutes in
+        let line = data.get_line_at(&at("2024-05-04T10:45:00+02:00")).unwrap();
+        assert_eq!((line.start.lat, line.start.lng), (48.8601, 2.3));
+        assert_eq!(line.start.relative_seconds, -300);
+        assert_eq!((line.end.lat, line.end.lng), (48.8625, 2.31));
+        assert_eq!(line.end.relative_seconds, 300);
+
+        let local = NaiveDateTime::parse_from_str("2024-05-04 10:15:00", "%Y-%m-%d %H:%M:%S");
+        assert_eq!(
+            data.utc_offset_at_local(&local.unwrap()),
+            FixedOffset::east_opt(2 * 3600)
+        );
+    }
+
+    #[test]
+    fn test_overflowing_path_offset() {
+        let data = Timeline::from_slice(
+            br#"[{
+                "startTime": "2024-05-04T10:30:00.000+02:00",
+                "endTime": "2024-05-04T12:30:00.000+02:00",
+                "timelinePath": [
+                    {"point": "geo:48.8601,2.3", "durationMinutesOffsetFromStartTime": "0"},
+                    {"point": "geo:48.8625,2.31", "durationMinutesOffsetFromStartTime": "10"},
+                    {"point": "geo:1,1", "durationMinutesOffsetFromStartTime": "9223372036854775807"}
+                ]
+            }]"#,
+        )
+        .unwrap();
+
+        let line = data.get_line_at(&at("2024-05-04T10:35:00+02:00")).unwrap();
+        assert_eq!((line.end.lat, line.end.lng), (48.8625, 2.31));
+        assert!(data.get_line_at(&at("2024-05-04T10:45:00+02:00")).is_none());
+    }
+}
@@ -0,0 +1,218 @@
+use chrono::{NaiveDate, NaiveTime, TimeDelta};
+use std::error::Error;
+
+use crate::{line::PointSource, track::TrackPoint};
+
+const METERS_PER_SECOND_PER_KNOT: f64 = 1852.0 / 3600.0;
+
+/// The fix being assembled from the sentences that share its time of day.
+/// Receivers send an RMC (with the date) and a GGA (with altitude and HDOP)
+/// for every fix.
+struct PendingFix {
+    time: NaiveTime,
+    date: Option<NaiveDate>,
+    lat: Option<f64>,
+    lng: Option<f64>,
+    altitude: Option<f64>,
+    accuracy: Option<f64>,
+    speed: Option<f64>,
+    valid: bool,
+}
+
+impl PendingFix {
+    fn new(time: NaiveTime, date: Option<NaiveDate>) -> Self {
+        PendingFix {
+            time,
+            date,
+            lat: None,
+            lng: None,
+            altitude: None,
+            accuracy: None,
+            speed: None,
+            valid: true,
+        }
+    }
+
+    fn into_track_point(self) -> Option<TrackPoint> {
+        if !self.valid {
+            return None;
+        }
+
+        Some(TrackPoint {
+            lat: self.lat?,
+            lng: self.lng?,
+            altitude: self.altitude,
+            accuracy: self.accuracy,
+            speed: self.speed,
+            timestamp: self.date?.and_time(self.time).and_utc(),
+            source: PointSource::Nmea,
+        })
+    }
+}
+
+/// Check the `*hh` checksum, the XOR of every byte between `$` and `*`, and
+/// return the sentence's fields
+fn checked_fields(line: &str) -> Option<Vec<&str>> {
+    let sentence = &line[line.find('$')? + 1..];
+    let (body, checksum) = sentence.split_once('*')?;
+
+    let expected = u8::from_str_radix(checksum.trim().get(..2)?, 16).ok()?;
+    let actual = body.bytes().fold(0, |sum, byte| sum ^ byte);
+
+    (actual == expected).then(|| body.split(',').collect())
+}
+
+/// Parse `ddmm.mmmm` (or `dddmm.mmmm`) and its hemisphere into degrees
+fn parse_coordinate(value: &str, hemisphere: &str) -> Option<f64> {
+    let value: f64 = value.parse().ok()?;
+    let degrees = (value / 100.0).trunc() + (value % 100.0) / 60.0;
+
+    match hemisphere {
+        "N" | "E" => Some(degrees),
+        "S" | "W" => Some(-degrees),
+        _ => None,
+    }
+}
+
+fn parse_time(value: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(value, "%H%M%S%.f").ok()
+}
+
+/// Estimated horizontal error from the GGA fix quality and HDOP: HDOP scaled
+/// by a typical range error for that kind of fix. `None` if there is no fix.
+fn estimate_accuracy(quality: &str, hdop: Option<f64>) -> Option<Option<f64>> {
+    let range_error = match quality {
+        "0" | "" => return None,
+        // Differential GPS, SBAS
+        "2" => 1.0,
+        // RTK fixed and float
+        "4" => 0.05,
+        "5" => 0.5,
+        // Dead reckoning
+        "6" => 50.0,
+        _ => 5.0,
+    };
+
+    Some(hdop.map(|hdop| hdop * range_error))
+}
+
+/// Parse the `RMC` and `GGA` sentences of an NMEA 0183 log, from any talker
+/// (`$GPRMC`, `$GNRMC`, `$GPGGA`...). Sentences with a bad checksum and
+/// fixes the receiver marks invalid are skipped. GGA sentences carry no date,
+/// so the date of the last RMC is used, moving on a day when the time of day
+/// wraps past midnight.
+pub fn parse(text: &str) -> Result<Vec<TrackPoint>, Box<dyn Error>> {
+    let mut points = Vec::new();
+    let mut date: Option<NaiveDate> = None;
+    let mut last_time: Option<NaiveTime> = None;
+    let mut pending: Option<PendingFix> = None;
+
+    for fields in text.lines().filter_map(checked_fields) {
+        let sentence = fields[0].get(2..).unwrap_or_default();
+        if sentence != "RMC" && sentence != "GGA" {
+            continue;
+        }
+        let Some(time) = fields.get(1).and_then(|time| parse_time(time)) else {
+            continue;
+        };
+
+        // Allow for sentences slightly out of order, but not half a day
+        if let (Some(last), Some(day)) = (last_time, date)
+            && last - time > TimeDelta::hours(12)
+        {
+            date = day.succ_opt();
+        }
+        last_time = Some(time);
+
+        if pending.as_ref().is_some_and(|fix| fix.time != time) {
+            points.extend(pending.take().and_then(PendingFix::into_track_point));
+        }
+        let fix = pending.get_or_insert_with(|| PendingFix::new(time, date));
+
+        let field = |index: usize| fields.get(index).copied().unwrap_or_default();
+        match sentence {
+            // time, status, lat, N/S, lng, E/W, speed, course, date
+            "RMC" => {
+                if let Ok(rmc_date) = NaiveDate::parse_from_str(field(9), "%d%m%y") {
+                    date = Some(rmc_date);
+                    fix.date = date;
+                }
+                fix.valid &= field(2) == "A";
+                fix.lat = parse_coordinate(field(3), field(4)).or(fix.lat);
+                fix.lng = parse_coordinate(field(5), field(6)).or(fix.lng);
+                fix.speed = field(7)
+                    .parse::<f64>()
+                    .ok()
+                    .map(|knots| knots * METERS_PER_SECOND_PER_KNOT);
+            }
+            // time, lat, N/S, lng, E/W, quality, satellites, HDOP, altitude
+            _ => {
+                match estimate_accuracy(field(6), field(8).parse().ok()) {
+                    Some(accuracy) => fix.accuracy = accuracy,
+                    None => fix.valid = false,
+                }
+                fix.lat = parse_coordinate(field(2), field(3)).or(fix.lat);
+                fix.lng = parse_coordinate(field(4), field(5)).or(fix.lng);
+                fix.altitude = field(9).parse().ok();
+            }
+        }
+    }
+    points.extend(pending.and_then(PendingFix::into_track_point));
+
+    Ok(points)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checked_fields() {
+        let fields =
+            checked_fields("$GPRMC,081836,A,3751.65,S,14507.36,E,000.0,360.0,130998,011.3,E*62")
+                .unwrap();
+        assert_eq!(fields[0], "GPRMC");
+        assert_eq!(fields[9], "130998");
+
+        assert!(
+            checked_fields("$GPRMC,081836,A,3751.65,S,14507.36,E,000.0,360.0,130998,011.3,E*63")
+                .is_none()
+        );
+        assert!(checked_fields("$GPRMC,081836,A,3751.65,S").is_none());
+    }
+
+    #[test]
+    fn test_parse() {
+        let text = std::fs::read_to_string("tests/track_example.nmea").unwrap();
+        let points = parse(&text).unwrap();
+
+        // RMC and GGA of the same fix are combined
+        assert!((points[0].lat - 54.7768).abs() < 1e-6);
+        assert!((points[0].lng - -1.5757).abs() < 1e-6);
+        assert_eq!(points[0].altitude, Some(42.5));
+        assert_eq!(points[0].accuracy, Some(4.5));
+        assert!((points[0].speed.unwrap() - 0.2572).abs() < 1e-4);
+        assert_eq!(
+            points[0].timestamp.to_rfc3339(),
+            "2025-08-11T23:59:58+00:00"
+        );
+
+        // The bad checksum and the void fix are skipped; the GGA-only fix
+        // after midnight is on the next day
+        assert_eq!(points[1].altitude, Some(43.0));
+        assert_eq!(
+            points[1].timestamp.to_rfc3339(),
+            "2025-08-12T00:00:01.500+00:00"
+        );
+
+        // DGPS fix from a multi-constellation receiver
+        assert_eq!(points[2].accuracy, Some(0.8));
+        assert_eq!(
+            points[2].timestamp.to_rfc3339(),
+            "2025-08-12T00:00:02+00:00"
+        );
+
+        assert_eq!(points.len(), 3);
+        assert!(points.iter().all(|point| point.source == PointSource::Nmea));
+    }
+}
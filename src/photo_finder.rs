@@ -0,0 +1,343 @@
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use std::{
+    collections::HashSet,
+    error::Error,
+    path::{Path, PathBuf},
+};
+use walkdir::WalkDir;
+
+/// File extensions the geotagger knows how to write EXIF tags to
+const PHOTO_EXTENSIONS: [&str; 3] = ["jpg", "jpeg", "png"];
+
+/// Whether `path` has one of the supported photo extensions
+pub fn is_supported_photo(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| {
+        let ext = ext.to_string_lossy().to_lowercase();
+        PHOTO_EXTENSIONS.contains(&ext.as_str())
+    })
+}
+
+/// The photos found, and the paths that couldn't be read while searching
+#[derive(Debug, Default)]
+pub struct FoundPhotos {
+    pub photos: Vec<PathBuf>,
+    pub unreadable: Vec<walkdir::Error>,
+}
+
+/// Collects the photos to geotag from a mix of files and directories
+pub struct PhotoFinder {
+    recursive: bool,
+    follow_symlinks: bool,
+    max_depth: Option<usize>,
+    include: Option<Patterns>,
+    exclude: Patterns,
+}
+
+/// Glob patterns as they read in a shell: patterns containing a `/` match
+/// the path, and `*` never crosses a `/`; other patterns match the name
+struct Patterns {
+    names: GlobSet,
+    paths: GlobSet,
+}
+
+impl Patterns {
+    fn new(patterns: &[String]) -> Result<Self, globset::Error> {
+        let mut names = GlobSetBuilder::new();
+        let mut paths = GlobSetBuilder::new();
+        for pattern in patterns {
+            let glob = GlobBuilder::new(pattern).literal_separator(true).build()?;
+            if pattern.contains('/') {
+                paths.add(glob);
+            } else {
+                names.add(glob);
+            }
+        }
+
+        Ok(Self {
+            names: names.build()?,
+            paths: paths.build()?,
+        })
+    }
+
+    fn is_match(&self, relative: &Path) -> bool {
+        relative
+            .file_name()
+            .is_some_and(|name| self.names.is_match(name))
+            || self.paths.is_match(relative)
+    }
+}
+
+impl PhotoFinder {
+    /// Create a finder with the given glob patterns. Patterns are matched
+    /// against paths relative to the directory being searched; a file given
+    /// directly matches if its path relative to any of its parent directories
+    /// does. With no include patterns every supported photo is included.
+    pub fn new(include: &[String], exclude: &[String]) -> Result<Self, globset::Error> {
+        let include = if include.is_empty() {
+            None
+        } else {
+            Some(Patterns::new(include)?)
+        };
+
+        Ok(Self {
+            recursive: false,
+            follow_symlinks: false,
+            max_depth: None,
+            include,
+            exclude: Patterns::new(exclude)?,
+        })
+    }
+
+    pub fn recursive(mut self, recursive: bool) -> Self {
+        self.recursive = recursive;
+        self
+    }
+
+    pub fn follow_symlinks(mut self, follow_symlinks: bool) -> Self {
+        self.follow_symlinks = follow_symlinks;
+        self
+    }
+
+    /// Limit how many directory levels below each directory are searched
+    pub fn max_depth(mut self, max_depth: Option<usize>) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Find the photos in `paths`. Paths below a directory that can't be read
+    /// are skipped and returned alongside the photos.
+    pub fn find(&self, paths: &[PathBuf]) -> Result<FoundPhotos, Box<dyn Error>> {
+        let mut found = FoundPhotos::default();
+
+        for path in paths {
+            if path.is_dir() {
+                self.find_in_directory(path, &mut found);
+            } else if path.is_file() {
+                if self.is_match(path, &trailing_paths(path)) {
+                    found.photos.push(path.clone());
+                }
+            } else {
+                return Err(format!("Path does not exist: {}", path.display()).into());
+            }
+        }
+
+        // The same photo may be reachable from several of the given paths
+        let mut seen = HashSet::new();
+        found.photos.retain(|photo| seen.insert(photo.clone()));
+
+        if found.photos.is_empty() {
+            return Err("No photos found in the specified paths".into());
+        }
+
+        Ok(found)
+    }
+
+    fn find_in_directory(&self, directory: &Path, found: &mut FoundPhotos) {
+        // Depth 0 is the directory itself, so its files are at depth 1
+        let max_depth = match (self.recursive, self.max_depth) {
+            (false, _) => 1,
+            (true, Some(max_depth)) => max_depth + 1,
+            (true, None) => usize::MAX,
+        };
+
+        let walker = WalkDir::new(directory)
+            .follow_links(self.follow_symlinks)
+            .max_depth(max_depth)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_entry(|entry| {
+                // Prune excluded directories instead of walking into them
+                entry.depth() == 0
+                    || !entry.file_type().is_dir()
+                    || !self.is_excluded(relative_path(directory, entry.path()))
+            });
+
+        for entry in walker {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    found.unreadable.push(e);
+                    continue;
+                }
+            };
+
+            if !entry.file_type().is_file() {
+                continue;
+            }
+
+            if self.is_match(entry.path(), &[relative_path(directory, entry.path())]) {
+                found.photos.push(entry.into_path());
+            }
+        }
+    }
+
+    /// Whether `path` is a photo to geotag, given the paths the patterns are
+    /// matched against
+    fn is_match(&self, path: &Path, relatives: &[&Path]) -> bool {
+        is_supported_photo(path)
+            && !relatives.iter().any(|relative| self.is_excluded(relative))
+            && self
+                .include
+                .as_ref()
+                .is_none_or(|include| relatives.iter().any(|relative| include.is_match(relative)))
+    }
+
+    fn is_excluded(&self, relative: &Path) -> bool {
+        self.exclude.is_match(relative)
+    }
+}
+
+fn relative_path<'a>(root: &Path, path: &'a Path) -> &'a Path {
+    path.strip_prefix(root).unwrap_or(path)
+}
+
+/// `path` relative to each of its parent directories, e.g. `b/c.jpg` and
+/// `a/b/c.jpg` for `a/b/c.jpg`
+fn trailing_paths(path: &Path) -> Vec<&Path> {
+    path.ancestors()
+        .skip(1)
+        .map(|parent| relative_path(parent, path))
+        .filter(|relative| !relative.as_os_str().is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn create_tree(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!(
+            "timeline_geotagger_{}_{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&root);
+
+        for file in [
+            "top.jpg",
+            "notes.txt",
+            "2023/08/29/IMG_0001.JPG",
+            "2023/08/29/IMG_0002.png",
+            "2023/08/30/IMG_0003.jpeg",
+            "2023/@eaDir/thumb.jpg",
+        ] {
+            let path = root.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, b"").unwrap();
+        }
+
+        root
+    }
+
+    fn relative_names(root: &Path, photos: &[PathBuf]) -> Vec<String> {
+        photos
+            .iter()
+            .map(|photo| {
+                relative_path(root, photo)
+                    .to_string_lossy()
+                    .replace('\\', "/")
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_find_top_level_only() {
+        let root = create_tree("top_level");
+        let photos = PhotoFinder::new(&[], &[])
+            .unwrap()
+            .find(std::slice::from_ref(&root))
+            .unwrap()
+            .photos;
+        assert_eq!(relative_names(&root, &photos), vec!["top.jpg"]);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_find_recursive_with_globs() {
+        let root = create_tree("recursive");
+
+        let photos = PhotoFinder::new(&[], &["**/@eaDir".into()])
+            .unwrap()
+            .recursive(true)
+            .find(std::slice::from_ref(&root))
+            .unwrap()
+            .photos;
+        assert_eq!(
+            relative_names(&root, &photos),
+            vec![
+                "2023/08/29/IMG_0001.JPG",
+                "2023/08/29/IMG_0002.png",
+                "2023/08/30/IMG_0003.jpeg",
+                "top.jpg",
+            ]
+        );
+
+        let photos = PhotoFinder::new(&["2023/08/29/*".into()], &["*.png".into()])
+            .unwrap()
+            .recursive(true)
+            .find(std::slice::from_ref(&root))
+            .unwrap()
+            .photos;
+        assert_eq!(
+            relative_names(&root, &photos),
+            vec!["2023/08/29/IMG_0001.JPG"]
+        );
+
+        // `*` doesn't cross directories
+        let result = PhotoFinder::new(&["2023/*".into()], &[])
+            .unwrap()
+            .recursive(true)
+            .find(std::slice::from_ref(&root));
+        assert!(result.is_err());
+
+        let photos = PhotoFinder::new(&[], &[])
+            .unwrap()
+            .recursive(true)
+            .max_depth(Some(2))
+            .find(std::slice::from_ref(&root))
+            .unwrap()
+            .photos;
+        assert_eq!(
+            relative_names(&root, &photos),
+            vec!["2023/@eaDir/thumb.jpg", "top.jpg"]
+        );
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_find_files_are_filtered() {
+        let root = create_tree("files");
+        let files = vec![
+            root.join("top.jpg"),
+            root.join("notes.txt"),
+            root.join("2023/08/29/IMG_0002.png"),
+        ];
+
+        let photos = PhotoFinder::new(&[], &["*.png".into()])
+            .unwrap()
+            .find(&files)
+            .unwrap()
+            .photos;
+        assert_eq!(relative_names(&root, &photos), vec!["top.jpg"]);
+
+        let result = PhotoFinder::new(&["*.png".into()], &[])
+            .unwrap()
+            .find(&[root.join("notes.txt")]);
+        assert!(result.is_err());
+
+        // Path patterns match a file given directly like one found in a walk
+        let photos = PhotoFinder::new(&["2023/08/29/*".into()], &[])
+            .unwrap()
+            .find(&files)
+            .unwrap()
+            .photos;
+        assert_eq!(
+            relative_names(&root, &photos),
+            vec!["2023/08/29/IMG_0002.png"]
+        );
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,170 @@
+use chrono::{DateTime, TimeDelta, Utc};
+
+use crate::{
+    line::{Line, Point, great_circle_distance},
+    track::TrackPoint,
+};
+
+/// Positions sorted by time, so the points around a photo are found with a
+/// binary search instead of parsing and scanning every fix
+#[derive(Debug, Default)]
+pub struct PointIndex {
+    points: Vec<TrackPoint>,
+}
+
+impl PointIndex {
+    pub fn new(mut points: Vec<TrackPoint>) -> Self {
+        points.sort_by_key(|point| point.timestamp);
+        PointIndex { points }
+    }
+
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    /// Keep only the points matching `keep`, which leaves them sorted
+    pub fn retain(&mut self, keep: impl FnMut(&TrackPoint) -> bool) {
+        self.points.retain(keep);
+    }
+
+    /// Add the points of `other`, keeping the whole index sorted
+    pub fn merge(&mut self, other: PointIndex) {
+        self.points.extend(other.points);
+        // Stable sort of two sorted runs, so this is a linear merge
+        self.points.sort_by_key(|point| point.timestamp);
+    }
+
+    /// The nearest points at least a second before and after `timestamp`
+    pub fn line_at(&self, timestamp: &DateTime<Utc>) -> Option<Line> {
+        let second = TimeDelta::seconds(1);
+        let before = self
+            .points
+            .partition_point(|point| point.timestamp <= *timestamp - second);
+        let after = self
+            .points
+            .partition_point(|point| point.timestamp < *timestamp + second);
+
+        let start = self.points[..before].last()?;
+        let end = self.points.get(after)?;
+
+        Some(Line::new(
+            start.to_point(timestamp),
+            end.to_point(timestamp),
+        ))
+    }
+
+    /// The point closest to `position` within `window` of `around`, with its
+    /// distance in meters
+    pub fn closest_to(
+        &self,
+        position: (f64, f64),
+        around: &DateTime<Utc>,
+        window: TimeDelta,
+    ) -> Option<(Point, f64)> {
+        let first = self
+            .points
+            .partition_point(|point| point.timestamp < *around - window);
+        let last = self
+            .points
+            .partition_point(|point| point.timestamp <= *around + window);
+
+        self.points[first..last]
+            .iter()
+            .map(|point| {
+                let distance = great_circle_distance(position, (point.lat, point.lng));
+                (point, distance)
+            })
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(point, distance)| (point.to_point(around), distance))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::line::PointSource;
+
+    fn index(seconds: &[i64], around: &DateTime<Utc>) -> PointIndex {
+        PointIndex::new(
+            seconds
+                .iter()
+                .map(|seconds| TrackPoint {
+                    lat: *seconds as f64 / 1000.0,
+                    lng: 0.0,
+                    altitude: None,
+                    accuracy: None,
+                    speed: None,
+                    timestamp: *around + TimeDelta::seconds(*seconds),
+                    source: PointSource::Gpx,
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_line_at() {
+        let around = DateTime::parse_from_rfc3339("2025-08-11T15:20:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let index = index(&[300, -30, 0, 90, -600, 30], &around);
+
+        let line = index.line_at(&around).unwrap();
+        assert_eq!(line.start.relative_seconds, -30);
+        assert_eq!(line.end.relative_seconds, 30);
+
+        let line = index.line_at(&(around + TimeDelta::seconds(100))).unwrap();
+        assert_eq!(line.start.relative_seconds, -10);
+        assert_eq!(line.end.relative_seconds, 200);
+
+        assert!(index.line_at(&(around + TimeDelta::seconds(300))).is_none());
+        assert!(index.line_at(&(around - TimeDelta::seconds(600))).is_none());
+        assert!(PointIndex::default().line_at(&around).is_none());
+    }
+
+    #[test]
+    fn test_merge() {
+        let around = DateTime::parse_from_rfc3339("2025-08-11T15:20:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let mut merged = index(&[-600, 0, 90], &around);
+        merged.merge(index(&[300, -30, 30], &around));
+        assert_eq!(merged.len(), 6);
+
+        let line = merged.line_at(&around).unwrap();
+        assert_eq!(line.start.relative_seconds, -30);
+        assert_eq!(line.end.relative_seconds, 30);
+
+        merged.retain(|point| point.timestamp != around - TimeDelta::seconds(30));
+        let line = merged.line_at(&around).unwrap();
+        assert_eq!(line.start.relative_seconds, -600);
+    }
+
+    #[test]
+    fn test_closest_to() {
+        let around = DateTime::parse_from_rfc3339("2025-08-11T15:20:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let index = index(&[-600, -30, 0, 30, 90], &around);
+
+        // -600 is the closest position, but outside the window
+        let (point, distance) = index
+            .closest_to((-0.6, 0.0), &around, TimeDelta::seconds(60))
+            .unwrap();
+        assert_eq!(point.relative_seconds, -30);
+        assert!(distance > 0.0);
+
+        assert!(
+            index
+                .closest_to(
+                    (0.0, 0.0),
+                    &(around + TimeDelta::hours(1)),
+                    TimeDelta::seconds(60)
+                )
+                .is_none()
+        );
+    }
+}
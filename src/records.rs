@@ -0,0 +1,118 @@
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::error::Error;
+
+use crate::{line::PointSource, track::TrackPoint};
+
+/// E7 coordinates wrap around in some old exports and need this subtracted
+const E7_OVERFLOW: i64 = 1 << 32;
+
+#[derive(Deserialize, Debug)]
+struct Records {
+    #[serde(rename = "locations")]
+    locations: Vec<RecordLocation>,
+}
+
+/// One entry of the legacy Takeout `Records.json`
+#[derive(Deserialize, Debug)]
+struct RecordLocation {
+    #[serde(rename = "latitudeE7")]
+    latitude_e7: Option<i64>,
+    #[serde(rename = "longitudeE7")]
+    longitude_e7: Option<i64>,
+    #[serde(rename = "timestamp")]
+    timestamp: Option<String>,
+    #[serde(rename = "timestampMs")]
+    timestamp_ms: Option<String>,
+    #[serde(rename = "accuracy")]
+    accuracy: Option<f64>,
+    #[serde(rename = "altitude")]
+    altitude: Option<f64>,
+}
+
+impl RecordLocation {
+    fn to_track_point(&self) -> Option<TrackPoint> {
+        Some(TrackPoint {
+            lat: from_e7(self.latitude_e7?, 90.0),
+            lng: from_e7(self.longitude_e7?, 180.0),
+            altitude: self.altitude,
+            // Fixes without an accuracy are stored as 0
+            accuracy: self.accuracy.filter(|accuracy| *accuracy > 0.0),
+            speed: None,
+            timestamp: parse_timestamp(self.timestamp.as_deref(), self.timestamp_ms.as_deref())?,
+            source: PointSource::RawSignal,
+        })
+    }
+}
+
+/// Takeout timestamps are either RFC 3339 strings or, in older exports,
+/// milliseconds since the epoch as a string
+pub(crate) fn parse_timestamp(
+    timestamp: Option<&str>,
+    timestamp_ms: Option<&str>,
+) -> Option<DateTime<Utc>> {
+    if let Some(timestamp) = timestamp {
+        return DateTime::parse_from_rfc3339(timestamp)
+            .ok()
+            .map(|timestamp| timestamp.to_utc());
+    }
+
+    let millis = timestamp_ms?.parse::<i64>().ok()?;
+    DateTime::from_timestamp_millis(millis)
+}
+
+/// Convert E7 fixed point degrees, undoing the wrap-around of some exports
+pub(crate) fn from_e7(value: i64, limit: f64) -> f64 {
+    let degrees = value as f64 / 1e7;
+    if degrees > limit {
+        (value - E7_OVERFLOW) as f64 / 1e7
+    } else {
+        degrees
+    }
+}
+
+/// Parse a legacy `Records.json` into position fixes. Entries without a
+/// position or timestamp are skipped.
+pub(crate) fn parse(bytes: &[u8]) -> Result<Vec<TrackPoint>, Box<dyn Error>> {
+    let records: Records = serde_json::from_slice(bytes)?;
+
+    Ok(records
+        .locations
+        .iter()
+        .filter_map(RecordLocation::to_track_point)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{line::PointSource, timeline::Timeline};
+    use chrono::{DateTime, Utc};
+
+    #[test]
+    fn test_records() {
+        let data = Timeline::from_path("tests/records_example.json").unwrap();
+
+        // Between a `timestamp` and a `timestampMs` entry
+        let timestamp = DateTime::parse_from_rfc3339("2013-06-30T16:12:03.523Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let line = data.get_line_at(&timestamp).unwrap();
+        assert_eq!(line.start.lat, 51.5074456);
+        assert_eq!(line.start.lng, -0.1277653);
+        assert_eq!(line.start.altitude, Some(35.0));
+        assert_eq!(line.start.relative_seconds, -10);
+        assert_eq!(line.end.lat, 51.5080012);
+        assert_eq!(line.end.lng, -0.1281234);
+        assert_eq!(line.end.altitude, None);
+        assert_eq!(line.end.relative_seconds, 10);
+        assert_eq!(line.start.source, PointSource::RawSignal);
+
+        // Wrapped-around E7 coordinates
+        let timestamp = DateTime::parse_from_rfc3339("2013-06-30T16:13:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let line = data.get_line_at(&timestamp).unwrap();
+        assert!((line.end.lat - -33.8688197).abs() < 1e-9);
+        assert!((line.end.lng - 151.2092955).abs() < 1e-9);
+    }
+}
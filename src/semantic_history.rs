@@ -0,0 +1,333 @@
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::error::Error;
+
+use crate::{
+    records::{from_e7, parse_timestamp},
+    timeline::TimedPosition,
+    timeline_json::ParseReport,
+    track::spread_by_distance,
+};
+
+/// One monthly file of the legacy Takeout `Semantic Location History`
+#[derive(Deserialize, Debug)]
+struct SemanticHistory {
+    #[serde(rename = "timelineObjects")]
+    timeline_objects: Vec<TimelineObject>,
+}
+
+/// An activity or a visit; objects of other kinds have neither and are
+/// skipped
+#[derive(Deserialize, Debug)]
+struct TimelineObject {
+    #[serde(rename = "activitySegment")]
+    activity_segment: Option<HistoryActivity>,
+    #[serde(rename = "placeVisit")]
+    place_visit: Option<HistoryVisit>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Duration {
+    #[serde(rename = "startTimestamp")]
+    start_timestamp: Option<String>,
+    #[serde(rename = "startTimestampMs")]
+    start_timestamp_ms: Option<String>,
+    #[serde(rename = "endTimestamp")]
+    end_timestamp: Option<String>,
+    #[serde(rename = "endTimestampMs")]
+    end_timestamp_ms: Option<String>,
+}
+
+impl Duration {
+    fn start(&self) -> Option<DateTime<Utc>> {
+        parse_timestamp(
+            self.start_timestamp.as_deref(),
+            self.start_timestamp_ms.as_deref(),
+        )
+    }
+
+    fn end(&self) -> Option<DateTime<Utc>> {
+        parse_timestamp(
+            self.end_timestamp.as_deref(),
+            self.end_timestamp_ms.as_deref(),
+        )
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct HistoryLocation {
+    #[serde(rename = "latitudeE7")]
+    latitude_e7: Option<i64>,
+    #[serde(rename = "longitudeE7")]
+    longitude_e7: Option<i64>,
+}
+
+impl HistoryLocation {
+    fn position(&self) -> Option<(f64, f64)> {
+        Some((
+            from_e7(self.latitude_e7?, 90.0),
+            from_e7(self.longitude_e7?, 180.0),
+        ))
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct Waypoint {
+    #[serde(rename = "latE7")]
+    lat_e7: i64,
+    #[serde(rename = "lngE7")]
+    lng_e7: i64,
+}
+
+#[derive(Deserialize, Debug)]
+struct WaypointPath {
+    #[serde(rename = "waypoints", default)]
+    waypoints: Vec<Waypoint>,
+}
+
+#[derive(Deserialize, Debug)]
+struct RawPathPoint {
+    #[serde(rename = "latE7")]
+    lat_e7: i64,
+    #[serde(rename = "lngE7")]
+    lng_e7: i64,
+    #[serde(rename = "timestamp")]
+    timestamp: Option<String>,
+    #[serde(rename = "timestampMs")]
+    timestamp_ms: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct SimplifiedRawPath {
+    #[serde(rename = "points", default)]
+    points: Vec<RawPathPoint>,
+}
+
+#[derive(Deserialize, Debug)]
+struct HistoryActivity {
+    #[serde(rename = "startLocation")]
+    start_location: Option<HistoryLocation>,
+    #[serde(rename = "endLocation")]
+    end_location: Option<HistoryLocation>,
+    #[serde(rename = "duration")]
+    duration: Duration,
+    #[serde(rename = "waypointPath")]
+    waypoint_path: Option<WaypointPath>,
+    #[serde(rename = "simplifiedRawPath")]
+    simplified_raw_path: Option<SimplifiedRawPath>,
+}
+
+#[derive(Deserialize, Debug)]
+struct HistoryVisit {
+    #[serde(rename = "location")]
+    location: Option<HistoryLocation>,
+    #[serde(rename = "centerLatE7")]
+    center_lat_e7: Option<i64>,
+    #[serde(rename = "centerLngE7")]
+    center_lng_e7: Option<i64>,
+    #[serde(rename = "duration")]
+    duration: Duration,
+}
+
+impl HistoryActivity {
+    /// The activity as a timed path. Points from `simplifiedRawPath` carry
+    /// their own timestamps; `waypointPath` points don't, so their times are
+    /// spread between the start and end time by distance travelled.
+    fn to_path(&self) -> Vec<TimedPosition> {
+        let (Some(start_time), Some(end_time)) = (self.duration.start(), self.duration.end())
+        else {
+            return Vec::new();
+        };
+        let start = self
+            .start_location
+            .as_ref()
+            .and_then(HistoryLocation::position);
+        let end = self
+            .end_location
+            .as_ref()
+            .and_then(HistoryLocation::position);
+
+        let raw_points = self
+            .simplified_raw_path
+            .iter()
+            .flat_map(|path| &path.points)
+            .filter_map(|point| {
+                let timestamp =
+                    parse_timestamp(point.timestamp.as_deref(), point.timestamp_ms.as_deref())?;
+                Some((
+                    from_e7(point.lat_e7, 90.0),
+                    from_e7(point.lng_e7, 180.0),
+                    timestamp,
+                ))
+            })
+            .collect::<Vec<_>>();
+
+        if raw_points.is_empty() {
+            let waypoints = self
+                .waypoint_path
+                .iter()
+                .flat_map(|path| &path.waypoints)
+                .map(|waypoint| {
+                    (
+                        from_e7(waypoint.lat_e7, 90.0),
+                        from_e7(waypoint.lng_e7, 180.0),
+                    )
+                });
+            let route = start
+                .into_iter()
+                .chain(waypoints)
+                .chain(end)
+                .collect::<Vec<_>>();
+
+            return route
+                .iter()
+                .zip(spread_by_distance(&route, start_time, end_time))
+                .map(|((lat, lng), timestamp)| (*lat, *lng, timestamp))
+                .collect();
+        }
+
+        let mut path = Vec::new();
+        if let Some((lat, lng)) = start {
+            path.push((lat, lng, start_time));
+        }
+        path.extend(raw_points);
+        if let Some((lat, lng)) = end {
+            path.push((lat, lng, end_time));
+        }
+
+        path
+    }
+}
+
+impl HistoryVisit {
+    /// The visit as a stationary path from its start to its end time
+    fn to_path(&self) -> Vec<TimedPosition> {
+        let position = self
+            .location
+            .as_ref()
+            .and_then(HistoryLocation::position)
+            .or_else(|| {
+                Some((
+                    from_e7(self.center_lat_e7?, 90.0),
+                    from_e7(self.center_lng_e7?, 180.0),
+                ))
+            });
+
+        match (position, self.duration.start(), self.duration.end()) {
+            (Some((lat, lng)), Some(start_time), Some(end_time)) => {
+                vec![(lat, lng, start_time), (lat, lng, end_time)]
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// The timed paths read from a Semantic Location History file
+#[derive(Debug, Default)]
+pub(crate) struct HistoryData {
+    /// One path per activity or visit
+    pub paths: Vec<Vec<TimedPosition>>,
+    pub report: ParseReport,
+}
+
+/// Parse one monthly Semantic Location History file into timed paths,
+/// counting the objects of unknown kinds
+pub(crate) fn parse(bytes: &[u8]) -> Result<HistoryData, Box<dyn Error>> {
+    let history: SemanticHistory = serde_json::from_slice(bytes)?;
+    let mut data = HistoryData::default();
+
+    for object in &history.timeline_objects {
+        let path = match (&object.activity_segment, &object.place_visit) {
+            (Some(activity), _) => activity.to_path(),
+            (None, Some(visit)) => visit.to_path(),
+            (None, None) => {
+                data.report.unknown_timeline_objects += 1;
+                continue;
+            }
+        };
+        if !path.is_empty() {
+            data.paths.push(path);
+        }
+    }
+
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{line::PointSource, timeline::Timeline};
+    use chrono::{DateTime, Utc};
+
+    fn at(timestamp: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(timestamp)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_semantic_history_directory() {
+        let data = Timeline::from_path("tests/semantic_history_example").unwrap();
+
+        // During a visit
+        let line = data.get_line_at(&at("2019-04-01T08:30:00Z")).unwrap();
+        assert_eq!(line.start.source, PointSource::SemanticPath);
+        assert_eq!((line.start.lat, line.start.lng), (48.8583701, 2.2944813));
+        assert_eq!((line.end.lat, line.end.lng), (48.8583701, 2.2944813));
+
+        // Along the simplified raw path of an activity
+        let line = data.get_line_at(&at("2019-04-01T09:12:00Z")).unwrap();
+        assert_eq!((line.start.lat, line.start.lng), (48.86, 2.31));
+        assert_eq!(line.start.relative_seconds, -120);
+        assert_eq!((line.end.lat, line.end.lng), (48.865, 2.32));
+        assert_eq!(line.end.relative_seconds, 180);
+
+        // Waypoints halfway along by distance get the halfway time
+        let line = data.get_line_at(&at("2019-05-02T10:29:00Z")).unwrap();
+        assert_eq!((line.end.lat, line.end.lng), (51.0, 0.5));
+        assert_eq!(line.end.relative_seconds, 60);
+    }
+
+    #[test]
+    fn test_unknown_timeline_objects() {
+        let data = Timeline::from_slice(
+            br#"{"timelineObjects": [
+                {"placeVisit": {
+                    "location": {"latitudeE7": 488583701, "longitudeE7": 22944813},
+                    "duration": {
+                        "startTimestamp": "2019-04-01T08:00:00Z",
+                        "endTimestamp": "2019-04-01T09:00:00Z"
+                    }
+                }},
+                {"tripSummary": {"distanceMeters": 1200}}
+            ]}"#,
+        )
+        .unwrap();
+
+        assert_eq!(data.parse_report().unknown_timeline_objects, 1);
+        let line = data.get_line_at(&at("2019-04-01T08:30:00Z")).unwrap();
+        assert_eq!((line.start.lat, line.start.lng), (48.8583701, 2.2944813));
+    }
+
+    #[test]
+    fn test_directory_with_unreadable_file() {
+        let directory = std::env::temp_dir().join(format!(
+            "timeline_geotagger_semantic_{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::copy(
+            "tests/semantic_history_example/2019/2019_APRIL.json",
+            directory.join("2019_APRIL.json"),
+        )
+        .unwrap();
+        std::fs::write(directory.join("2019_MAY.json"), "{\"timelineObjects\": [").unwrap();
+
+        let data = Timeline::from_path(&directory);
+        std::fs::remove_dir_all(&directory).unwrap();
+        let data = data.unwrap();
+
+        assert_eq!(data.parse_report().unreadable_files.len(), 1);
+        assert!(data.parse_report().unreadable_files[0].contains("2019_MAY.json"));
+        assert!(data.get_line_at(&at("2019-04-01T08:30:00Z")).is_some());
+    }
+}
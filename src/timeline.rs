@@ -1,268 +1,254 @@
-use chrono::{DateTime, Utc};
-use serde::Deserialize;
-use std::{error::Error, fs::File, path::Path};
-
-use crate::line::{Line, LineBuilder, Point};
-
-#[derive(Deserialize, Debug)]
-struct FrequentPlace {
-    #[serde(rename = "placeId")]
-    _place_id: String,
-    #[serde(rename = "placeLocation")]
-    _place_location: String,
-    #[serde(rename = "label")]
-    _label: Option<String>,
+use chrono::{DateTime, FixedOffset, NaiveDateTime, TimeDelta, Utc};
+use serde::{Deserialize, de::IgnoredAny};
+use std::{
+    error::Error,
+    fs::{self, File},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+use walkdir::WalkDir;
+
+use crate::{
+    csv_track::{self, CsvFormat},
+    fit, geojson, gpx, kml,
+    line::{Line, MaxGap, Point, PointSource},
+    mobile_export, nmea,
+    point_index::PointIndex,
+    records, semantic_history,
+    timeline_json::{self, ParseReport},
+    track::TrackPoint,
+};
+
+/// Extensions of the files loaded from a location history directory
+const LOCATION_FILE_EXTENSIONS: [&str; 9] = [
+    "json", "gpx", "kml", "kmz", "nmea", "nma", "fit", "csv", "geojson",
+];
+
+fn has_extension(path: &Path, extension: &str) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case(extension))
 }
 
-#[derive(Deserialize, Debug)]
-struct ModeDistribution {
-    #[serde(rename = "mode")]
-    _mode: String,
-    #[serde(rename = "rate")]
-    _rate: f64,
+/// A position at a moment in time, as loaded from other location history
+/// formats
+pub type TimedPosition = (f64, f64, DateTime<Utc>);
+
+/// The span of an activity or visit segment with the UTC offsets recorded at
+/// its start and end
+#[derive(Debug, Clone)]
+pub(crate) struct UtcOffsetSpan {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub offset_minutes: [i32; 2],
 }
 
-#[derive(Deserialize, Debug)]
-struct FrequentTrip {
-    #[serde(rename = "waypointIds")]
-    _waypoint_ids: Vec<String>,
-    #[serde(rename = "modeDistribution")]
-    _mode_distribution: Vec<ModeDistribution>,
-    #[serde(rename = "startTimeMinutes")]
-    _start_time_minutes: u32,
-    #[serde(rename = "endTimeMinutes")]
-    _end_time_minutes: u32,
-    #[serde(rename = "durationMinutes")]
-    _duration_minutes: u32,
-    #[serde(rename = "confidence")]
-    _confidence: f64,
-    #[serde(rename = "commuteDirection")]
-    _commute_direction: String,
+/// The location history formats stored as a JSON object
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum JsonFormat {
+    Records,
+    SemanticHistory,
+    GeoJson,
+    TimelineJson,
 }
 
-#[derive(Deserialize, Debug)]
-struct ModeAffinity {
-    #[serde(rename = "mode")]
-    _mode: String,
-    #[serde(rename = "affinity")]
-    _affinity: f64,
-}
+impl JsonFormat {
+    /// The format a top-level key belongs to, if it is one that tells
+    fn from_key(key: &str) -> Option<Self> {
+        match key {
+            "locations" => Some(JsonFormat::Records),
+            "timelineObjects" => Some(JsonFormat::SemanticHistory),
+            // GeoJSON `FeatureCollection`, or the geometry of a single `Feature`
+            "features" | "geometry" => Some(JsonFormat::GeoJson),
+            "semanticSegments" | "rawSignals" | "userLocationProfile" => {
+                Some(JsonFormat::TimelineJson)
+            }
+            _ => None,
+        }
+    }
 
-#[derive(Deserialize, Debug)]
-struct Persona {
-    #[serde(rename = "travelModeAffinities")]
-    _travel_mode_affinities: Vec<ModeAffinity>,
-}
+    /// Read the top-level keys of `bytes` up to the first that tells the
+    /// format, skipping the values before it, so that a large export isn't
+    /// read once to find its format and again to parse it. Objects without
+    /// such a key are taken as Timeline.json.
+    fn detect(bytes: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let skip_whitespace = |position: usize| {
+            position
+                + bytes[position..]
+                    .iter()
+                    .take_while(|byte| byte.is_ascii_whitespace())
+                    .count()
+        };
 
-#[derive(Deserialize, Debug)]
-struct UserLocationProfile {
-    #[serde(rename = "frequentPlaces")]
-    _frequent_places: Option<Vec<FrequentPlace>>,
-    #[serde(rename = "frequentTrips")]
-    _frequent_trips: Option<Vec<FrequentTrip>>,
-    #[serde(rename = "persona")]
-    _persona: Option<Persona>,
-}
+        let mut position = skip_whitespace(0);
+        if bytes.get(position) != Some(&b'{') {
+            return Err("Expected a JSON object or array".into());
+        }
+        position += 1;
+
+        loop {
+            position = skip_whitespace(position);
+            match bytes.get(position) {
+                None | Some(b'}') => return Ok(JsonFormat::TimelineJson),
+                Some(b',') => {
+                    position += 1;
+                    continue;
+                }
+                _ => {}
+            }
 
-#[derive(Deserialize, Debug)]
-struct TimelinePoint {
-    point: String,
-    time: String,
-}
+            let (key, length) = next_json_value::<String>(&bytes[position..])?;
+            if let Some(format) = Self::from_key(&key) {
+                return Ok(format);
+            }
 
-#[derive(Deserialize, Debug)]
-struct LocationStruct {
-    #[serde(rename = "latLng")]
-    _lat_lng: String,
+            position = skip_whitespace(position + length);
+            if bytes.get(position) != Some(&b':') {
+                return Err(format!("Expected ':' after \"{}\"", key).into());
+            }
+            let (IgnoredAny, length) = next_json_value(&bytes[position + 1..])?;
+            position += 1 + length;
+        }
+    }
 }
 
-#[derive(Deserialize, Debug)]
-struct ActivityCandidate {
-    #[serde(rename = "type")]
-    _activity_type: String,
-    #[serde(rename = "probability")]
-    _probability: f64,
+/// Parse the JSON value at the start of `bytes`, with the number of bytes
+/// it took up
+fn next_json_value<'de, T: Deserialize<'de>>(
+    bytes: &'de [u8],
+) -> Result<(T, usize), Box<dyn Error>> {
+    let mut values = serde_json::Deserializer::from_slice(bytes).into_iter::<T>();
+    match values.next() {
+        Some(value) => Ok((value?, values.byte_offset())),
+        None => Err("Unexpected end of JSON".into()),
+    }
 }
 
-#[derive(Deserialize, Debug)]
-struct ActivitySegment {
-    #[serde(rename = "start")]
-    _start: LocationStruct,
-    #[serde(rename = "end")]
-    _end: LocationStruct,
-    #[serde(rename = "distanceMeters")]
-    _distance_meters: f64,
-    #[serde(rename = "topCandidate")]
-    _top_candidate: ActivityCandidate,
+/// The only times positions are kept for, e.g. the days around the photos
+#[derive(Debug, Clone, Copy)]
+pub struct TimeWindow {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
 }
 
-#[derive(Deserialize, Debug)]
-struct PlaceCandidate {
-    #[serde(rename = "placeId")]
-    _place_id: String,
-    #[serde(rename = "semanticType")]
-    _semantic_type: String,
-    #[serde(rename = "probability")]
-    _probability: f64,
-    #[serde(rename = "placeLocation")]
-    _place_location: LocationStruct,
+impl TimeWindow {
+    pub fn contains(&self, timestamp: &DateTime<Utc>) -> bool {
+        self.start <= *timestamp && *timestamp <= self.end
+    }
 }
 
-#[derive(Deserialize, Debug)]
-struct VisitSegment {
-    #[serde(rename = "hierarchyLevel")]
-    _hierarchy_level: i32,
-    #[serde(rename = "probability")]
-    _probability: f64,
-    #[serde(rename = "topCandidate")]
-    _top_candidate: PlaceCandidate,
+/// How to read location files whose layout varies from file to file
+#[derive(Debug, Clone, Default)]
+pub struct SourceOptions {
+    pub csv: CsvFormat,
+    /// Drop positions outside this window while loading
+    pub time_window: Option<TimeWindow>,
+    /// Drop fixes whose recorded accuracy is worse than this many meters,
+    /// e.g. cell tower fixes
+    pub max_accuracy: Option<f64>,
 }
 
-#[derive(Deserialize, Debug)]
-struct Place {
-    #[serde(rename = "placeId")]
-    _place_id: String,
+/// The positions of one or more location sources, parsed once when loaded
+#[derive(Debug)]
+pub struct Timeline {
+    /// Raw signals and the points of GPX and other track files, sorted by
+    /// time for lookups
+    raw: PointIndex,
+    /// Points on semantic paths, used where there are no raw points
+    semantic: PointIndex,
+    utc_offsets: Vec<UtcOffsetSpan>,
+    /// What was skipped while loading this source
+    report: ParseReport,
+    /// Weight of this source's fixes against other sources: each step up
+    /// halves their estimated error
+    priority: i32,
+    /// Sources added with `add_source`, kept apart from this one
+    other_sources: Vec<Timeline>,
 }
 
-#[derive(Deserialize, Debug)]
-struct PlaceIdentifier {
-    #[serde(rename = "identifier")]
-    _identifier: Place,
+/// Priority given to one `--timeline` source, from `PATH=PRIORITY`
+#[derive(Debug, Clone)]
+pub struct SourcePriority {
+    pub path: PathBuf,
+    pub priority: i32,
 }
 
-#[derive(Deserialize, Debug)]
-struct Trip {
-    #[serde(rename = "distanceFromOriginKms")]
-    _distance_from_origin_kms: i32,
-    #[serde(rename = "destinations")]
-    _destinations: Vec<PlaceIdentifier>,
+impl FromStr for SourcePriority {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (path, priority) = value
+            .rsplit_once('=')
+            .ok_or_else(|| format!("Expected PATH=PRIORITY, got \"{}\"", value))?;
+        let priority = priority
+            .trim()
+            .parse()
+            .map_err(|_| format!("Invalid priority \"{}\", expected a whole number", priority))?;
+
+        Ok(SourcePriority {
+            path: PathBuf::from(path),
+            priority,
+        })
+    }
 }
 
-#[derive(Deserialize, Debug)]
-struct Note {
-    #[serde(rename = "note")]
-    _note: String,
-}
+impl Timeline {
+    /// Load a location history file, or every file in a directory such as
+    /// Takeout's `Semantic Location History`
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        Self::from_path_with(path, &SourceOptions::default())
+    }
 
-#[derive(Deserialize, Debug)]
-#[serde(untagged)]
-enum TimelineMemory {
-    Trip {
-        #[serde(rename = "trip")]
-        _trip: Trip,
-    },
-    Note {
-        #[serde(rename = "note")]
-        _note: Note,
-    },
-}
+    /// Load a location history file or directory, reading CSV files as
+    /// described by `options` and keeping only positions in its time window
+    pub fn from_path_with<P: AsRef<Path>>(
+        path: P,
+        options: &SourceOptions,
+    ) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref();
+        if path.is_dir() {
+            return Self::from_dir(path, options);
+        }
 
-#[derive(Deserialize, Debug)]
-#[serde(untagged)]
-enum SemanticSegment {
-    Path {
-        #[serde(rename = "startTime")]
-        _start_time: String,
-        #[serde(rename = "endTime")]
-        _end_time: String,
-        #[serde(rename = "timelinePath")]
-        timeline_path: Vec<TimelinePoint>,
-    },
-    Activity {
-        #[serde(rename = "startTime")]
-        _start_time: String,
-        #[serde(rename = "endTime")]
-        _end_time: String,
-        #[serde(rename = "startTimeTimezoneUtcOffsetMinutes")]
-        _start_time_timezone_utc_offset_minutes: i32,
-        #[serde(rename = "endTimeTimezoneUtcOffsetMinutes")]
-        _end_time_timezone_utc_offset_minutes: i32,
-        #[serde(rename = "activity")]
-        _activity: ActivitySegment,
-    },
-    PlaceVisit {
-        #[serde(rename = "startTime")]
-        _start_time: String,
-        #[serde(rename = "endTime")]
-        _end_time: String,
-        #[serde(rename = "startTimeTimezoneUtcOffsetMinutes")]
-        _start_time_timezone_utc_offset_minutes: i32,
-        #[serde(rename = "endTimeTimezoneUtcOffsetMinutes")]
-        _end_time_timezone_utc_offset_minutes: i32,
-        #[serde(rename = "visit")]
-        _visit: VisitSegment,
-    },
-    Memory {
-        #[serde(rename = "startTime")]
-        _start_time: String,
-        #[serde(rename = "endTime")]
-        _end_time: String,
-        #[serde(rename = "timelineMemory")]
-        _timeline_memory: TimelineMemory,
-    },
-}
+        let mut timeline = Self::from_file(path, options)?;
+        if let Some(window) = &options.time_window {
+            timeline.retain_within(window);
+        }
+        if let Some(max_accuracy) = options.max_accuracy {
+            timeline.drop_inaccurate(max_accuracy);
+        }
+        Ok(timeline)
+    }
 
-#[derive(Deserialize, Debug)]
-struct WifiDeviceRecord {
-    #[serde(rename = "mac")]
-    _mac: u64, // MAC address as a number
-    #[serde(rename = "rawRssi")]
-    _raw_rssi: i32, // Received Signal Strength Indicator
-}
+    fn from_file(path: &Path, options: &SourceOptions) -> Result<Self, Box<dyn Error>> {
+        if has_extension(path, "gpx") {
+            let points = gpx::parse(&fs::read_to_string(path)?)?;
+            return Ok(Self::from_track_points(points));
+        }
 
-#[derive(Deserialize, Debug)]
-struct ProbableActivity {
-    #[serde(rename = "type")]
-    _activity_type: String,
-    #[serde(rename = "confidence")]
-    _confidence: f64,
-}
+        if has_extension(path, "kml") {
+            let points = kml::parse(&fs::read_to_string(path)?)?;
+            return Ok(Self::from_track_points(points));
+        }
 
-#[derive(Deserialize, Debug)]
-enum RawSignal {
-    #[serde(rename = "position")]
-    Position {
-        #[serde(rename = "LatLng")]
-        lat_lng: String,
-        #[serde(rename = "accuracyMeters")]
-        _accuracy_meters: i32,
-        #[serde(rename = "altitudeMeters")]
-        altitude_meters: Option<f64>,
-        #[serde(rename = "source")]
-        _source: String,
-        #[serde(rename = "timestamp")]
-        timestamp: String,
-        #[serde(rename = "speedMetersPerSecond")]
-        _speed_meters_per_second: Option<f64>,
-    },
-    #[serde(rename = "wifiScan")]
-    WifiScan {
-        #[serde(rename = "deliveryTime")]
-        _delivery_time: String,
-        #[serde(rename = "devicesRecords")]
-        _devices_records: Vec<WifiDeviceRecord>,
-    },
-    #[serde(rename = "activityRecord")]
-    Activity {
-        #[serde(rename = "probableActivities")]
-        _probable_activities: Vec<ProbableActivity>,
-        #[serde(rename = "timestamp")]
-        _timestamp: String,
-    },
-}
+        if has_extension(path, "kmz") {
+            return Ok(Self::from_track_points(kml::parse_kmz(path)?));
+        }
 
-#[derive(Deserialize, Debug)]
-pub struct Timeline {
-    #[serde(rename = "semanticSegments")]
-    semantic_segments: Vec<SemanticSegment>,
-    #[serde(rename = "rawSignals")]
-    raw_signals: Vec<RawSignal>,
-    #[serde(rename = "userLocationProfile")]
-    _user_location_profile: UserLocationProfile,
-}
+        if has_extension(path, "nmea") || has_extension(path, "nma") {
+            // Loggers sometimes write binary junk between sentences
+            let points = nmea::parse(&String::from_utf8_lossy(&fs::read(path)?))?;
+            return Ok(Self::from_track_points(points));
+        }
+
+        if has_extension(path, "fit") {
+            return Ok(Self::from_track_points(fit::parse(&fs::read(path)?)?));
+        }
+
+        if has_extension(path, "csv") {
+            let points = csv_track::parse(&fs::read(path)?, &options.csv)?;
+            return Ok(Self::from_track_points(points));
+        }
 
-impl Timeline {
-    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
         // Stolen from https://github.com/paritytech/substrate/pull/10137
 
         let file = File::open(path)?;
@@ -271,78 +257,288 @@ impl Timeline {
         let bytes = unsafe {
             memmap2::Mmap::map(&file).map_err(|e| format!("Error mmaping timeline file: {}", e))?
         };
-        let timeline_data = serde_json::from_slice(&bytes)?;
 
-        Ok(timeline_data)
+        Self::from_slice_within(&bytes, options.time_window.as_ref())
     }
 
-    pub fn get_point_at(&self, timestamp: &DateTime<Utc>) -> Result<Point, String> {
-        let mut line = self.get_line_from_raw_signals(timestamp);
+    /// Parse any supported location history export, detecting its format
+    pub fn from_slice(bytes: &[u8]) -> Result<Self, Box<dyn Error>> {
+        Self::from_slice_within(bytes, None)
+    }
+
+    /// Parse a location history export, skipping positions of a Timeline.json
+    /// outside `window` as they are read
+    pub fn from_slice_within(
+        bytes: &[u8],
+        window: Option<&TimeWindow>,
+    ) -> Result<Self, Box<dyn Error>> {
+        // Only the mobile app export has an array at the top level
+        if bytes.iter().find(|byte| !byte.is_ascii_whitespace()) == Some(&b'[') {
+            let data = mobile_export::parse(bytes)?;
+            return Ok(Self {
+                utc_offsets: data.utc_offsets,
+                ..Self::from_parts(Vec::new(), data.paths)
+            });
+        }
+
+        match JsonFormat::detect(bytes)? {
+            JsonFormat::Records => Ok(Self::from_parts(records::parse(bytes)?, Vec::new())),
+            JsonFormat::GeoJson => Ok(Self::from_track_points(geojson::parse(bytes)?)),
+            JsonFormat::SemanticHistory => {
+                let data = semantic_history::parse(bytes)?;
+                Ok(Self {
+                    report: data.report,
+                    ..Self::from_parts(Vec::new(), data.paths)
+                })
+            }
+            JsonFormat::TimelineJson => {
+                let data = timeline_json::parse(bytes, window)?;
+                Ok(Self {
+                    semantic: PointIndex::new(data.path_points),
+                    utc_offsets: data.utc_offsets,
+                    report: data.report,
+                    ..Self::from_track_points(data.raw_points)
+                })
+            }
+        }
+    }
+
+    /// Load and merge every supported file below `directory`. Files that
+    /// can't be loaded are skipped and listed in the parse report.
+    fn from_dir(directory: &Path, options: &SourceOptions) -> Result<Self, Box<dyn Error>> {
+        let mut timeline = Self::from_parts(Vec::new(), Vec::new());
+        let mut loaded = 0;
+
+        for entry in WalkDir::new(directory).sort_by_file_name() {
+            let entry = entry?;
+            let is_supported = LOCATION_FILE_EXTENSIONS
+                .iter()
+                .any(|extension| has_extension(entry.path(), extension));
+            if !entry.file_type().is_file() || !is_supported {
+                continue;
+            }
 
-        if line.is_none() {
-            line = self.get_line_from_semantic_segments(timestamp);
+            match Self::from_path_with(entry.path(), options) {
+                Ok(file) => {
+                    timeline.merge(file);
+                    loaded += 1;
+                }
+                Err(e) => timeline.report.unreadable_files.push(format!(
+                    "{}: {}",
+                    entry.path().display(),
+                    e
+                )),
+            }
+        }
+
+        if loaded == 0 {
+            return Err(match timeline.report.unreadable_files.first() {
+                Some(error) => error.clone().into(),
+                None => format!("No location files found in {}", directory.display()).into(),
+            });
         }
 
-        match line {
+        Ok(timeline)
+    }
+
+    fn from_parts(raw_points: Vec<TrackPoint>, paths: Vec<Vec<TimedPosition>>) -> Self {
+        Self {
+            raw: PointIndex::new(raw_points),
+            semantic: PointIndex::new(
+                paths
+                    .into_iter()
+                    .flatten()
+                    .map(|(lat, lng, timestamp)| TrackPoint {
+                        lat,
+                        lng,
+                        altitude: None,
+                        accuracy: None,
+                        speed: None,
+                        timestamp,
+                        source: PointSource::SemanticPath,
+                    })
+                    .collect(),
+            ),
+            utc_offsets: Vec::new(),
+            report: ParseReport::default(),
+            priority: 0,
+            other_sources: Vec::new(),
+        }
+    }
+
+    fn from_track_points(track_points: Vec<TrackPoint>) -> Self {
+        Self::from_parts(track_points, Vec::new())
+    }
+
+    /// Drop the positions outside `window`
+    fn retain_within(&mut self, window: &TimeWindow) {
+        self.raw.retain(|point| window.contains(&point.timestamp));
+        self.semantic
+            .retain(|point| window.contains(&point.timestamp));
+    }
+
+    /// Drop the fixes with a recorded accuracy worse than `max_accuracy`
+    /// meters, counting them in the parse report. Fixes without one are
+    /// kept.
+    fn drop_inaccurate(&mut self, max_accuracy: f64) {
+        let is_accurate = |point: &TrackPoint| point.accuracy.is_none_or(|a| a <= max_accuracy);
+        let count = self.raw.len() + self.semantic.len();
+
+        self.raw.retain(is_accurate);
+        self.semantic.retain(is_accurate);
+        self.report.inaccurate_positions += count - self.raw.len() - self.semantic.len();
+    }
+
+    /// Add the locations of `other` to this source, e.g. the next month of
+    /// Semantic Location History
+    pub fn merge(&mut self, other: Timeline) {
+        self.raw.merge(other.raw);
+        self.semantic.merge(other.semantic);
+        self.utc_offsets.extend(other.utc_offsets);
+        self.report.merge(other.report);
+        self.other_sources.extend(other.other_sources);
+    }
+
+    /// What was skipped while loading this source, not counting those added
+    /// with `add_source`
+    pub fn parse_report(&self) -> &ParseReport {
+        &self.report
+    }
+
+    pub fn with_priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Add `other` as a separate source, e.g. a second phone's export or a
+    /// GPX track. Lines are never drawn between the fixes of different
+    /// sources, which may disagree; the source whose line around a photo has
+    /// the smallest estimated error, weighted by priority, is used.
+    pub fn add_source(&mut self, mut other: Timeline) {
+        let nested = std::mem::take(&mut other.other_sources);
+        self.other_sources.push(other);
+        self.other_sources.extend(nested);
+    }
+
+    /// This source and every one added to it
+    fn sources(&self) -> impl Iterator<Item = &Timeline> {
+        std::iter::once(self).chain(&self.other_sources)
+    }
+
+    pub fn get_point_at(&self, timestamp: &DateTime<Utc>) -> Result<Point, String> {
+        match self.get_line_at(timestamp) {
             Some(line) => line.get_point_at(timestamp),
             None => Err("No valid line found".into()),
         }
     }
 
-    fn get_line_from_raw_signals(&self, timestamp: &DateTime<Utc>) -> Option<Line> {
-        let mut line_builder = LineBuilder::new();
-
-        for raw_signal in &self.raw_signals {
-            let RawSignal::Position {
-                lat_lng,
-                altitude_meters,
-                timestamp: raw_timestamp,
-                _accuracy_meters: _,
-                _source: _,
-                _speed_meters_per_second: _,
-            } = raw_signal
-            else {
-                continue; // Skip non-position signals
-            };
-
-            let point = Point::from_timeline(lat_lng, raw_timestamp, altitude_meters, timestamp);
-
-            if let Ok(point) = point {
-                line_builder.add_point(point);
-            }
+    /// Find the pair of points surrounding `timestamp` in the source where
+    /// they give the smallest estimated error, weighted by priority
+    pub fn get_line_at(&self, timestamp: &DateTime<Utc>) -> Option<Line> {
+        self.select_line(timestamp, |_| true)
+    }
+
+    /// Find the pair of points surrounding `timestamp` like `get_line_at`,
+    /// but only from the lines within `max_gap`, so a source or semantic path
+    /// with closer fixes is used when the best line has too large a gap. When
+    /// no line is within it, the reason the best line was rejected is
+    /// returned.
+    pub fn get_line_within(
+        &self,
+        timestamp: &DateTime<Utc>,
+        max_gap: &MaxGap,
+    ) -> Option<Result<Line, String>> {
+        if let Some(line) = self.select_line(timestamp, |line| line.check_gap(max_gap).is_ok()) {
+            return Some(Ok(line));
         }
 
-        line_builder.build()
+        let line = self.get_line_at(timestamp)?;
+        Some(line.check_gap(max_gap).map(|()| line))
     }
 
-    fn get_line_from_semantic_segments(&self, timestamp: &DateTime<Utc>) -> Option<Line> {
-        let mut line_builder = LineBuilder::new();
-
-        for segment in &self.semantic_segments {
-            let SemanticSegment::Path {
-                _start_time: _,
-                _end_time: _,
-                timeline_path,
-            } = segment
-            else {
-                continue; // Skip non-position signals
-            };
-
-            for point in timeline_path.iter() {
-                let TimelinePoint {
-                    point: lat_lng,
-                    time: point_timestamp,
-                } = point;
-
-                let point = Point::from_timeline(&lat_lng, point_timestamp, &None, timestamp);
-
-                if let Ok(point) = point {
-                    line_builder.add_point(point);
+    /// The `accept`ed line around `timestamp` with the smallest weighted
+    /// estimated error across all sources
+    fn select_line(
+        &self,
+        timestamp: &DateTime<Utc>,
+        accept: impl Fn(&Line) -> bool,
+    ) -> Option<Line> {
+        self.sources()
+            .filter_map(|source| {
+                let line = source.get_source_line_at(timestamp, &accept)?;
+                let weighted_error = line.estimated_error() / 2f64.powi(source.priority);
+                Some((line, weighted_error))
+            })
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(line, _)| line)
+    }
+
+    /// Find the `accept`ed pair of points surrounding `timestamp` in this
+    /// source alone, preferring raw signals over semantic segments
+    fn get_source_line_at(
+        &self,
+        timestamp: &DateTime<Utc>,
+        accept: impl Fn(&Line) -> bool,
+    ) -> Option<Line> {
+        self.get_line_from_raw_signals(timestamp)
+            .filter(&accept)
+            .or_else(|| {
+                self.get_line_from_semantic_segments(timestamp)
+                    .filter(&accept)
+            })
+    }
+
+    /// Work out the UTC offset that was in effect at local time `local`, from
+    /// the offsets recorded on the activity and visit segments covering it.
+    /// Returns `None` if no segment covers it or the segments disagree.
+    pub fn utc_offset_at_local(&self, local: &NaiveDateTime) -> Option<FixedOffset> {
+        let mut offsets = Vec::new();
+
+        for span in self.sources().flat_map(|source| &source.utc_offsets) {
+            // A segment may span a timezone change, so try both of its offsets
+            for offset_minutes in span.offset_minutes {
+                let Some(offset) = FixedOffset::east_opt(offset_minutes * 60) else {
+                    continue;
+                };
+
+                let utc = local.and_utc() - TimeDelta::seconds(offset.local_minus_utc() as i64);
+                if span.start <= utc && utc <= span.end && !offsets.contains(&offset) {
+                    offsets.push(offset);
                 }
             }
         }
 
-        line_builder.build()
+        match offsets.as_slice() {
+            [offset] => Some(*offset),
+            _ => None,
+        }
+    }
+
+    /// Find the timeline point closest to `position` within `window` of
+    /// `around`, with its distance in meters
+    pub fn get_closest_point_to(
+        &self,
+        position: (f64, f64),
+        around: &DateTime<Utc>,
+        window: TimeDelta,
+    ) -> Option<(Point, f64)> {
+        self.sources()
+            .flat_map(|source| {
+                [
+                    source.raw.closest_to(position, around, window),
+                    source.semantic.closest_to(position, around, window),
+                ]
+            })
+            .flatten()
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+    }
+
+    fn get_line_from_raw_signals(&self, timestamp: &DateTime<Utc>) -> Option<Line> {
+        self.raw.line_at(timestamp)
+    }
+
+    fn get_line_from_semantic_segments(&self, timestamp: &DateTime<Utc>) -> Option<Line> {
+        self.semantic.line_at(timestamp)
     }
 }
 
@@ -362,13 +558,44 @@ mod tests {
 
         let data = result.unwrap();
         assert!(
-            !data.semantic_segments.is_empty(),
-            "Semantic Segments should not be empty"
+            !data.semantic.is_empty(),
+            "Semantic paths should not be empty"
         );
+        assert!(!data.raw.is_empty(), "Raw Signals should not be empty");
         assert!(
-            !data.raw_signals.is_empty(),
-            "Raw Signals should not be empty"
+            !data.utc_offsets.is_empty(),
+            "UTC offsets should not be empty"
+        );
+    }
+
+    #[test]
+    fn test_detect_format() {
+        let detect = |json: &str| JsonFormat::detect(json.as_bytes()).unwrap();
+
+        assert_eq!(detect(r#"{"locations": []}"#), JsonFormat::Records);
+        assert_eq!(
+            detect(r#" {"type": "Feature", "geometry": {}}"#),
+            JsonFormat::GeoJson
         );
+        assert_eq!(
+            detect(r#"{"timelineObjects": []}"#),
+            JsonFormat::SemanticHistory
+        );
+        assert_eq!(
+            detect(r#"{"semanticSegments": []}"#),
+            JsonFormat::TimelineJson
+        );
+        assert_eq!(detect("{}"), JsonFormat::TimelineJson);
+
+        // Values before the telling key are skipped, and nothing after it is
+        // read
+        assert_eq!(
+            detect(r#"{"other": {"a": "}\"", "b": [1, 2.5e3]}, "n": -1, "locations": [{"#),
+            JsonFormat::Records
+        );
+
+        assert!(JsonFormat::detect(b"\"text\"").is_err());
+        assert!(JsonFormat::detect(br#"{"other": [1, 2"#).is_err());
     }
 
     #[test]
@@ -445,10 +672,11 @@ mod tests {
         let point = data.get_point_at(&timestamp);
         assert!(point.is_ok());
         let point = point.unwrap();
-        assert_eq!(point.lat, 54.797363759183675);
-        assert_eq!(point.lng, -1.5921443040816325);
+        assert!((point.lat - 54.797363759183696).abs() < 1e-9);
+        assert!((point.lng - -1.5921443040815213).abs() < 1e-9);
         assert_eq!(point.altitude, Some(75.5999984741211));
         assert_eq!(point.relative_seconds, 0);
+        assert_eq!(point.source, PointSource::RawSignal);
 
         // From semantic segments
         let timestamp = DateTime::parse_from_rfc3339("2023-08-29T12:37:20.000+01:00")
@@ -458,9 +686,266 @@ mod tests {
         let point = data.get_point_at(&timestamp);
         assert!(point.is_ok());
         let point = point.unwrap();
-        assert_eq!(point.lat, 50.144777355555554);
-        assert_eq!(point.lng, 5.601500588888889);
+        assert!((point.lat - 50.14477737703536).abs() < 1e-9);
+        assert!((point.lng - 5.601500538331497).abs() < 1e-9);
         assert_eq!(point.altitude, None);
         assert_eq!(point.relative_seconds, 0);
+        assert_eq!(point.source, PointSource::SemanticPath);
+    }
+
+    #[test]
+    fn test_line_within_max_gap() {
+        let path = "tests/basic_example.json";
+        let data = Timeline::from_path(path).unwrap();
+
+        // Semantic path fixes 20s before and 160s after
+        let timestamp = DateTime::parse_from_rfc3339("2023-08-29T12:37:20.000+01:00")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        let line = data
+            .get_line_within(
+                &timestamp,
+                &MaxGap {
+                    total: Some(180),
+                    ..MaxGap::default()
+                },
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!(line.start.relative_seconds, -20);
+
+        // With no line within the gap, the preferred line's reason is given
+        let reason = data
+            .get_line_within(
+                &timestamp,
+                &MaxGap {
+                    after: Some(60),
+                    ..MaxGap::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(
+            reason.err().as_deref(),
+            Some("nearest fix after is 160s later (max 60s)")
+        );
+
+        // Before any recorded location
+        let timestamp = DateTime::parse_from_rfc3339("2000-01-01T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert!(
+            data.get_line_within(&timestamp, &MaxGap::default())
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_utc_offset_at_local() {
+        let path = "tests/basic_example.json";
+        let data = Timeline::from_path(path).unwrap();
+
+        // Covered by the activity segment recorded with a +02:00 offset
+        let local =
+            NaiveDateTime::parse_from_str("2023-08-29 13:20:00", "%Y-%m-%d %H:%M:%S").unwrap();
+        assert_eq!(
+            data.utc_offset_at_local(&local),
+            FixedOffset::east_opt(2 * 3600)
+        );
+
+        // Not covered by any segment
+        let local =
+            NaiveDateTime::parse_from_str("2020-01-01 10:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+        assert_eq!(data.utc_offset_at_local(&local), None);
+    }
+
+    /// A source with fixes `seconds` from `around`, all with `accuracy`
+    fn track(around: &DateTime<Utc>, seconds: &[i64], accuracy: f64, lat: f64) -> Timeline {
+        Timeline::from_track_points(
+            seconds
+                .iter()
+                .map(|seconds| TrackPoint {
+                    lat,
+                    lng: 0.0,
+                    altitude: None,
+                    accuracy: Some(accuracy),
+                    speed: None,
+                    timestamp: *around + TimeDelta::seconds(*seconds),
+                    source: PointSource::Gpx,
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_sources() {
+        let photo_time = DateTime::parse_from_rfc3339("2025-08-11T15:20:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        // A coarse phone fix a minute away beats a precise logger fix two
+        // minutes away: 50 + 84 m against 5 + 168 m
+        let mut data = track(&photo_time, &[-60, 60], 50.0, 1.0);
+        data.add_source(track(&photo_time, &[-120, 120], 5.0, 2.0));
+        assert_eq!(data.get_point_at(&photo_time).unwrap().lat, 1.0);
+
+        // Unless the logger has priority
+        let mut data = track(&photo_time, &[-60, 60], 50.0, 1.0);
+        data.add_source(track(&photo_time, &[-120, 120], 5.0, 2.0).with_priority(1));
+        assert_eq!(data.get_point_at(&photo_time).unwrap().lat, 2.0);
+
+        // Fixes from different sources are never joined
+        let mut data = track(&photo_time, &[-60], 5.0, 1.0);
+        data.add_source(track(&photo_time, &[60], 5.0, 2.0));
+        assert!(data.get_line_at(&photo_time).is_none());
+
+        // Merged files are one source
+        let mut data = track(&photo_time, &[-60], 5.0, 1.0);
+        data.merge(track(&photo_time, &[60], 5.0, 1.0));
+        assert!(data.get_line_at(&photo_time).is_some());
+    }
+
+    #[test]
+    fn test_line_within_max_gap_across_sources() {
+        let photo_time = DateTime::parse_from_rfc3339("2025-08-11T15:20:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let max_gap = MaxGap {
+            total: Some(300),
+            ..MaxGap::default()
+        };
+
+        // A precise source with a long gap is passed over for one within it
+        let mut data = track(&photo_time, &[-600, 600], 1.0, 1.0).with_priority(5);
+        data.add_source(track(&photo_time, &[-120, 120], 50.0, 2.0));
+        assert_eq!(data.get_line_at(&photo_time).unwrap().start.lat, 1.0);
+        let line = data
+            .get_line_within(&photo_time, &max_gap)
+            .unwrap()
+            .unwrap();
+        assert_eq!(line.start.lat, 2.0);
+    }
+
+    #[test]
+    fn test_max_accuracy() {
+        let photo_time = DateTime::parse_from_rfc3339("2025-08-11T15:20:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        // A cell tower fix right by the photo and GPS fixes further away
+        let mut data = track(&photo_time, &[-60, 120], 8.0, 1.0);
+        data.merge(track(&photo_time, &[-5], 1500.0, 2.0));
+        let line = data.get_line_at(&photo_time).unwrap();
+        assert_eq!(line.start.accuracy, Some(1500.0));
+
+        data.drop_inaccurate(200.0);
+        let point = data.get_point_at(&photo_time).unwrap();
+        assert_eq!(point.lat, 1.0);
+        // The fix a minute before plus a minute's walk
+        assert_eq!(point.estimated_error, Some(8.0 + 60.0 * 1.4));
+        assert_eq!(data.parse_report().inaccurate_positions, 1);
+    }
+
+    #[test]
+    fn test_source_priority_from_str() {
+        let source = SourcePriority::from_str("C:\\rides\\a=b.gpx=-2").unwrap();
+        assert_eq!(source.path, PathBuf::from("C:\\rides\\a=b.gpx"));
+        assert_eq!(source.priority, -2);
+
+        assert!(SourcePriority::from_str("ride.gpx").is_err());
+        assert!(SourcePriority::from_str("ride.gpx=high").is_err());
+    }
+
+    /// Nearest fixes around `timestamp` found the way lookups worked before
+    /// the index: parsing and scanning every raw signal's text
+    fn linear_line_at(
+        raw_signals: &[(String, String)],
+        timestamp: &DateTime<Utc>,
+    ) -> Option<(i64, i64)> {
+        let relative_seconds = raw_signals.iter().filter_map(|(lat_lng, time)| {
+            Point::parse_lat_lng(lat_lng)?;
+            let time = DateTime::parse_from_rfc3339(time).ok()?;
+            Some((time.to_utc() - *timestamp).num_seconds())
+        });
+        let (mut start, mut end) = (None, None);
+        for seconds in relative_seconds {
+            if seconds < 0 && start.is_none_or(|start| start < seconds) {
+                start = Some(seconds);
+            }
+            if seconds > 0 && end.is_none_or(|end| end > seconds) {
+                end = Some(seconds);
+            }
+        }
+        start.zip(end)
+    }
+
+    /// Compare lookups through the index with the linear scan they replaced.
+    /// Run with `cargo test --release -- --ignored --nocapture bench_`
+    #[test]
+    #[ignore]
+    fn bench_point_index() {
+        const SIGNALS: i64 = 100_000;
+        const PHOTOS: i64 = 200;
+
+        let start = DateTime::parse_from_rfc3339("2015-01-01T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let raw_points: Vec<_> = (0..SIGNALS)
+            .map(|i| TrackPoint {
+                lat: 50.0 + (i % 1000) as f64 / 1000.0,
+                lng: -1.5,
+                altitude: Some(40.0),
+                accuracy: Some(10.0),
+                speed: None,
+                timestamp: start + TimeDelta::seconds(i * 97),
+                source: PointSource::RawSignal,
+            })
+            .collect();
+        // The raw signals as they were kept before being parsed on loading
+        let raw_signals: Vec<_> = raw_points
+            .iter()
+            .map(|point| {
+                (
+                    format!("{}°, {}°", point.lat, point.lng),
+                    point
+                        .timestamp
+                        .to_rfc3339_opts(chrono::SecondsFormat::Millis, false),
+                )
+            })
+            .collect();
+        let data = Timeline::from_parts(raw_points, Vec::new());
+
+        // Spread the photos over the whole timeline
+        let photo_times: Vec<_> = (0..PHOTOS)
+            .map(|i| start + TimeDelta::seconds(i * 7919 % (SIGNALS * 97)))
+            .collect();
+
+        let timer = std::time::Instant::now();
+        let linear: Vec<_> = photo_times
+            .iter()
+            .map(|timestamp| linear_line_at(&raw_signals, timestamp))
+            .collect();
+        let linear_time = timer.elapsed();
+
+        let timer = std::time::Instant::now();
+        let indexed: Vec<_> = photo_times
+            .iter()
+            .map(|timestamp| {
+                data.get_line_at(timestamp)
+                    .map(|line| (line.start.relative_seconds, line.end.relative_seconds))
+            })
+            .collect();
+        let indexed_time = timer.elapsed();
+
+        println!(
+            "{} photos, {} raw signals: linear scan {:?}, index {:?} ({:.0}x faster)",
+            PHOTOS,
+            SIGNALS,
+            linear_time,
+            indexed_time,
+            linear_time.as_secs_f64() / indexed_time.as_secs_f64()
+        );
+        assert_eq!(linear, indexed);
+        assert!(indexed_time < linear_time);
     }
 }
@@ -0,0 +1,588 @@
+use chrono::{DateTime, Utc};
+use serde::{
+    Deserialize, Deserializer,
+    de::{DeserializeSeed, IgnoredAny, MapAccess, SeqAccess, Visitor},
+};
+use std::{borrow::Cow, collections::BTreeMap, error::Error, fmt};
+
+use crate::{
+    line::{Point, PointSource},
+    timeline::{TimeWindow, UtcOffsetSpan},
+    track::TrackPoint,
+};
+
+/// Top level keys of an on-device `Timeline.json`. `userLocationProfile` and
+/// anything else are skipped.
+#[derive(Deserialize)]
+#[serde(field_identifier, rename_all = "camelCase")]
+enum Section {
+    SemanticSegments,
+    RawSignals,
+    #[serde(other)]
+    Other,
+}
+
+/// A string borrowed from the file, or copied if it had to be unescaped
+struct Text<'a>(Cow<'a, str>);
+
+impl<'de: 'a, 'a> Deserialize<'de> for Text<'a> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct TextVisitor;
+
+        impl<'de> Visitor<'de> for TextVisitor {
+            type Value = Text<'de>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a string")
+            }
+
+            fn visit_borrowed_str<E>(self, value: &'de str) -> Result<Text<'de>, E> {
+                Ok(Text(Cow::Borrowed(value)))
+            }
+
+            fn visit_str<E>(self, value: &str) -> Result<Text<'de>, E> {
+                Ok(Text(Cow::Owned(value.to_string())))
+            }
+        }
+
+        deserializer.deserialize_str(TextVisitor)
+    }
+}
+
+/// The parts of a semantic segment that are used. Other fields are skipped,
+/// and any of these may be missing.
+#[derive(Deserialize)]
+struct Segment<'a> {
+    #[serde(rename = "startTime", borrow)]
+    start_time: Option<Text<'a>>,
+    #[serde(rename = "endTime", borrow)]
+    end_time: Option<Text<'a>>,
+    #[serde(rename = "startTimeTimezoneUtcOffsetMinutes")]
+    start_offset_minutes: Option<i32>,
+    #[serde(rename = "endTimeTimezoneUtcOffsetMinutes")]
+    end_offset_minutes: Option<i32>,
+    #[serde(rename = "timelinePath", borrow)]
+    timeline_path: Option<Vec<PathPoint<'a>>>,
+    activity: Option<IgnoredAny>,
+    visit: Option<IgnoredAny>,
+    #[serde(rename = "timelineMemory")]
+    timeline_memory: Option<IgnoredAny>,
+}
+
+#[derive(Deserialize)]
+struct PathPoint<'a> {
+    #[serde(borrow)]
+    point: Option<Text<'a>>,
+    #[serde(borrow)]
+    time: Option<Text<'a>>,
+}
+
+/// A raw signal, tagged by its only key. Wifi scans, activity records and
+/// kinds added in later exports are skipped without being read into memory.
+enum RawSignal<'a> {
+    Position(Position<'a>),
+    Unused,
+    Unknown(Cow<'a, str>),
+}
+
+impl<'de: 'a, 'a> Deserialize<'de> for RawSignal<'a> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct RawSignalVisitor;
+
+        impl<'de> Visitor<'de> for RawSignalVisitor {
+            type Value = RawSignal<'de>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a raw signal object")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<RawSignal<'de>, A::Error> {
+                let mut signal = RawSignal::Unknown(Cow::Borrowed("{}"));
+
+                while let Some(Text(kind)) = map.next_key()? {
+                    match kind.as_ref() {
+                        "position" => signal = RawSignal::Position(map.next_value()?),
+                        "wifiScan" | "activityRecord" => {
+                            map.next_value::<IgnoredAny>()?;
+                            signal = RawSignal::Unused;
+                        }
+                        _ => {
+                            map.next_value::<IgnoredAny>()?;
+                            if matches!(signal, RawSignal::Unknown(_)) {
+                                signal = RawSignal::Unknown(kind);
+                            }
+                        }
+                    }
+                }
+
+                Ok(signal)
+            }
+        }
+
+        deserializer.deserialize_map(RawSignalVisitor)
+    }
+}
+
+#[derive(Deserialize)]
+struct Position<'a> {
+    #[serde(rename = "LatLng", borrow)]
+    lat_lng: Option<Text<'a>>,
+    #[serde(borrow)]
+    timestamp: Option<Text<'a>>,
+    #[serde(rename = "altitudeMeters")]
+    altitude_meters: Option<f64>,
+    #[serde(rename = "accuracyMeters")]
+    accuracy_meters: Option<f64>,
+}
+
+/// What was skipped while loading a source, so that changes in the export
+/// format show up as warnings rather than failures
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ParseReport {
+    /// Files of a directory that couldn't be loaded, with the reason
+    pub unreadable_files: Vec<String>,
+    /// Top level arrays that weren't in the file
+    pub missing_sections: Vec<&'static str>,
+    /// Raw signals of unknown kinds, counted by kind
+    pub unknown_raw_signals: BTreeMap<String, usize>,
+    /// Semantic segments that are not a path, activity, visit or memory
+    pub unknown_segments: usize,
+    /// Semantic Location History objects that are not an activity or visit
+    pub unknown_timeline_objects: usize,
+    /// Positions without a readable `LatLng`/`point` or timestamp
+    pub unreadable_positions: usize,
+    /// Fixes dropped for being less accurate than the maximum accuracy
+    pub inaccurate_positions: usize,
+}
+
+impl ParseReport {
+    pub fn is_empty(&self) -> bool {
+        *self == ParseReport::default()
+    }
+
+    /// Add the counts of `other`, e.g. another file of the same source
+    pub fn merge(&mut self, other: ParseReport) {
+        self.unreadable_files.extend(other.unreadable_files);
+        for section in other.missing_sections {
+            if !self.missing_sections.contains(&section) {
+                self.missing_sections.push(section);
+            }
+        }
+        for (kind, count) in other.unknown_raw_signals {
+            *self.unknown_raw_signals.entry(kind).or_default() += count;
+        }
+        self.unknown_segments += other.unknown_segments;
+        self.unknown_timeline_objects += other.unknown_timeline_objects;
+        self.unreadable_positions += other.unreadable_positions;
+        self.inaccurate_positions += other.inaccurate_positions;
+    }
+
+    /// One line per kind of thing that was ignored
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings: Vec<_> = self
+            .unreadable_files
+            .iter()
+            .map(|file| format!("unreadable file {}", file))
+            .collect();
+        warnings.extend(
+            self.missing_sections
+                .iter()
+                .map(|section| format!("no {} section", section)),
+        );
+        warnings.extend(
+            self.unknown_raw_signals.iter().map(|(kind, count)| {
+                format!("{} raw signal(s) of unknown kind \"{}\"", count, kind)
+            }),
+        );
+        if self.unknown_segments > 0 {
+            warnings.push(format!(
+                "{} semantic segment(s) of unknown kind",
+                self.unknown_segments
+            ));
+        }
+        if self.unknown_timeline_objects > 0 {
+            warnings.push(format!(
+                "{} timeline object(s) of unknown kind",
+                self.unknown_timeline_objects
+            ));
+        }
+        if self.unreadable_positions > 0 {
+            warnings.push(format!(
+                "{} position(s) without a readable location or time",
+                self.unreadable_positions
+            ));
+        }
+        if self.inaccurate_positions > 0 {
+            warnings.push(format!(
+                "{} fix(es) less accurate than the maximum accuracy",
+                self.inaccurate_positions
+            ));
+        }
+        warnings
+    }
+}
+
+/// The positions and UTC offsets read from a `Timeline.json`
+#[derive(Debug, Default)]
+pub(crate) struct TimelineData {
+    pub raw_points: Vec<TrackPoint>,
+    pub path_points: Vec<TrackPoint>,
+    pub utc_offsets: Vec<UtcOffsetSpan>,
+    pub report: ParseReport,
+}
+
+/// Collects what is kept of each segment and signal as it is read
+struct Loader<'w> {
+    data: TimelineData,
+    window: Option<&'w TimeWindow>,
+}
+
+impl Loader<'_> {
+    fn in_window(&self, timestamp: &DateTime<Utc>) -> bool {
+        self.window.is_none_or(|window| window.contains(timestamp))
+    }
+
+    /// Keep `point` if it could be read and is in the window
+    fn keep(&mut self, point: Option<TrackPoint>) -> Option<TrackPoint> {
+        let Some(point) = point else {
+            self.data.report.unreadable_positions += 1;
+            return None;
+        };
+        self.in_window(&point.timestamp).then_some(point)
+    }
+
+    fn add_segment(&mut self, segment: Segment) {
+        let is_known = segment.timeline_path.is_some()
+            || segment.activity.is_some()
+            || segment.visit.is_some()
+            || segment.timeline_memory.is_some();
+        if !is_known {
+            self.data.report.unknown_segments += 1;
+            return;
+        }
+
+        for point in segment.timeline_path.into_iter().flatten() {
+            let point = parse_position(
+                point.point.as_ref(),
+                point.time.as_ref(),
+                None,
+                None,
+                PointSource::SemanticPath,
+            );
+            if let Some(point) = self.keep(point) {
+                self.data.path_points.push(point);
+            }
+        }
+
+        let (Some(start_offset), Some(end_offset)) =
+            (segment.start_offset_minutes, segment.end_offset_minutes)
+        else {
+            return;
+        };
+        let (Some(start), Some(end)) = (
+            parse_time(segment.start_time.as_ref()),
+            parse_time(segment.end_time.as_ref()),
+        ) else {
+            return;
+        };
+        let span = UtcOffsetSpan {
+            start,
+            end,
+            offset_minutes: [start_offset, end_offset],
+        };
+        let overlaps = self
+            .window
+            .is_none_or(|window| span.start <= window.end && window.start <= span.end);
+        if overlaps {
+            self.data.utc_offsets.push(span);
+        }
+    }
+
+    fn add_raw_signal(&mut self, signal: RawSignal) {
+        let position = match signal {
+            RawSignal::Position(position) => position,
+            RawSignal::Unused => return,
+            RawSignal::Unknown(kind) => {
+                *self
+                    .data
+                    .report
+                    .unknown_raw_signals
+                    .entry(kind.into_owned())
+                    .or_default() += 1;
+                return;
+            }
+        };
+
+        // A fix can't be exact, so an accuracyMeters of 0 or less means the
+        // accuracy is unknown
+        let accuracy = position.accuracy_meters.filter(|accuracy| *accuracy > 0.0);
+
+        let point = parse_position(
+            position.lat_lng.as_ref(),
+            position.timestamp.as_ref(),
+            position.altitude_meters,
+            accuracy,
+            PointSource::RawSignal,
+        );
+        if let Some(point) = self.keep(point) {
+            self.data.raw_points.push(point);
+        }
+    }
+}
+
+/// Reads the whole document, handing each section to its own seed
+impl<'de> Visitor<'de> for &mut Loader<'_> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a Timeline.json object")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        let (mut has_segments, mut has_raw_signals) = (false, false);
+
+        while let Some(section) = map.next_key()? {
+            match section {
+                Section::SemanticSegments => {
+                    map.next_value_seed(Segments(&mut *self))?;
+                    has_segments = true;
+                }
+                Section::RawSignals => {
+                    map.next_value_seed(RawSignals(&mut *self))?;
+                    has_raw_signals = true;
+                }
+                Section::Other => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+
+        // Either section may be missing, but a file with neither is probably
+        // not a Timeline.json at all
+        if !has_segments && !has_raw_signals {
+            return Err(serde::de::Error::custom(
+                "expected semanticSegments or rawSignals",
+            ));
+        }
+        if !has_segments {
+            self.data.report.missing_sections.push("semanticSegments");
+        }
+        if !has_raw_signals {
+            self.data.report.missing_sections.push("rawSignals");
+        }
+        Ok(())
+    }
+}
+
+/// The `semanticSegments` array, handled one segment at a time
+struct Segments<'l, 'w>(&'l mut Loader<'w>);
+
+impl<'de> DeserializeSeed<'de> for Segments<'_, '_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de> Visitor<'de> for Segments<'_, '_> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an array of semantic segments")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        while let Some(segment) = seq.next_element::<Segment<'de>>()? {
+            self.0.add_segment(segment);
+        }
+        Ok(())
+    }
+}
+
+/// The `rawSignals` array, handled one signal at a time
+struct RawSignals<'l, 'w>(&'l mut Loader<'w>);
+
+impl<'de> DeserializeSeed<'de> for RawSignals<'_, '_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de> Visitor<'de> for RawSignals<'_, '_> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an array of raw signals")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        while let Some(signal) = seq.next_element::<RawSignal<'de>>()? {
+            self.0.add_raw_signal(signal);
+        }
+        Ok(())
+    }
+}
+
+fn parse_time(time: Option<&Text>) -> Option<DateTime<Utc>> {
+    let timestamp = DateTime::parse_from_rfc3339(&time?.0).ok()?;
+    Some(timestamp.to_utc())
+}
+
+fn parse_position(
+    lat_lng: Option<&Text>,
+    timestamp: Option<&Text>,
+    altitude: Option<f64>,
+    accuracy: Option<f64>,
+    source: PointSource,
+) -> Option<TrackPoint> {
+    let (lat, lng) = Point::parse_lat_lng(&lat_lng?.0)?;
+
+    Some(TrackPoint {
+        lat,
+        lng,
+        altitude,
+        accuracy,
+        speed: None,
+        timestamp: parse_time(timestamp)?,
+        source,
+    })
+}
+
+/// Parse an on-device `Timeline.json` in one pass over `bytes`, keeping only
+/// the positions, within `window` if given, and the UTC offsets of segments.
+/// Strings are borrowed from `bytes` rather than copied, and wifi scans,
+/// activity records and the user location profile are skipped unread.
+/// Unknown signals and segments, missing fields and a missing section are
+/// tolerated and counted in the data's report.
+pub(crate) fn parse(
+    bytes: &[u8],
+    window: Option<&TimeWindow>,
+) -> Result<TimelineData, Box<dyn Error>> {
+    let mut loader = Loader {
+        data: TimelineData::default(),
+        window,
+    };
+
+    let mut deserializer = serde_json::Deserializer::from_slice(bytes);
+    deserializer.deserialize_map(&mut loader)?;
+    deserializer.end()?;
+
+    Ok(loader.data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timestamp(value: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(value).unwrap().to_utc()
+    }
+
+    #[test]
+    fn test_parse() {
+        let bytes = std::fs::read("tests/basic_example.json").unwrap();
+        let data = parse(&bytes, None).unwrap();
+
+        assert!(!data.raw_points.is_empty());
+        assert!(!data.path_points.is_empty());
+        assert!(!data.utc_offsets.is_empty());
+        assert!(
+            data.raw_points
+                .iter()
+                .all(|point| point.source == PointSource::RawSignal)
+        );
+    }
+
+    #[test]
+    fn test_parse_within_window() {
+        let bytes = std::fs::read("tests/basic_example.json").unwrap();
+        let window = TimeWindow {
+            start: timestamp("2025-08-11T00:00:00Z"),
+            end: timestamp("2025-08-12T00:00:00Z"),
+        };
+        let data = parse(&bytes, Some(&window)).unwrap();
+
+        assert!(!data.raw_points.is_empty());
+        assert!(
+            data.raw_points
+                .iter()
+                .chain(&data.path_points)
+                .all(|point| window.contains(&point.timestamp))
+        );
+        // The semantic paths are all from 2023
+        assert!(data.path_points.is_empty());
+    }
+
+    #[test]
+    fn test_parse_skips_unused_signals() {
+        let json = r#"{
+            "semanticSegments": [],
+            "rawSignals": [
+                {"wifiScan": {"deliveryTime": "2025-08-11T15:20:00Z", "devicesRecords": []}},
+                {"position": {"LatLng": "54.1°, -1.5°", "timestamp": "2025-08-11T15:20:00Z"}},
+                {"activityRecord": {"probableActivities": [], "timestamp": "2025-08-11T15:20:00Z"}}
+            ],
+            "userLocationProfile": {"frequentPlaces": []}
+        }"#;
+        let data = parse(json.as_bytes(), None).unwrap();
+
+        assert_eq!(data.raw_points.len(), 1);
+        assert_eq!(data.raw_points[0].lat, 54.1);
+        assert_eq!(data.raw_points[0].accuracy, None);
+        assert!(data.report.is_empty());
+    }
+
+    #[test]
+    fn test_parse_tolerates_unknown_and_missing() {
+        let json = r#"{
+            "semanticSegments": [
+                {"startTime": "2025-08-11T15:00:00Z", "endTime": "2025-08-11T16:00:00Z", "parking": {}},
+                {"timelinePath": [
+                    {"point": "54.2°, -1.5°", "time": "2025-08-11T15:20:00Z"},
+                    {"point": "54.3°, -1.5°"}
+                ]}
+            ],
+            "rawSignals": [
+                {"bleScan": {"devices": [{"id": 1}]}},
+                {"bleScan": {}},
+                {"position": {"LatLng": "54.1°, -1.5°", "timestamp": "2025-08-11T15:20:00Z", "accuracyMeters": 12.5}},
+                {"position": {"timestamp": "2025-08-11T15:21:00Z"}}
+            ]
+        }"#;
+        let data = parse(json.as_bytes(), None).unwrap();
+
+        assert_eq!(data.raw_points.len(), 1);
+        assert_eq!(data.raw_points[0].accuracy, Some(12.5));
+        assert_eq!(data.path_points.len(), 1);
+        assert_eq!(data.path_points[0].lat, 54.2);
+        assert_eq!(data.report.unknown_raw_signals["bleScan"], 2);
+        assert_eq!(data.report.unknown_segments, 1);
+        assert_eq!(data.report.unreadable_positions, 2);
+        assert!(data.report.missing_sections.is_empty());
+        assert_eq!(
+            data.report.warnings(),
+            [
+                "2 raw signal(s) of unknown kind \"bleScan\"",
+                "1 semantic segment(s) of unknown kind",
+                "2 position(s) without a readable location or time",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_missing_sections() {
+        let json = r#"{"rawSignals": [{"position": {"LatLng": "54.1°, -1.5°", "timestamp": "2025-08-11T15:20:00Z"}}]}"#;
+        let data = parse(json.as_bytes(), None).unwrap();
+        assert_eq!(data.raw_points.len(), 1);
+        assert_eq!(data.report.missing_sections, ["semanticSegments"]);
+
+        let data = parse(br#"{"semanticSegments": []}"#, None).unwrap();
+        assert_eq!(data.report.missing_sections, ["rawSignals"]);
+
+        // Not a Timeline.json at all
+        assert!(parse(br#"{"userLocationProfile": {}}"#, None).is_err());
+    }
+}
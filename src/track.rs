@@ -0,0 +1,61 @@
+use chrono::{DateTime, TimeDelta, Utc};
+
+use crate::line::{Point, PointSource, great_circle_distance};
+
+/// A timestamped position, parsed once when its source is loaded: a raw
+/// signal or semantic path point, or a fix from a GPX file or other track
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrackPoint {
+    pub lat: f64,
+    pub lng: f64,
+    pub altitude: Option<f64>,
+    /// Estimated horizontal error in meters
+    pub accuracy: Option<f64>,
+    /// Ground speed in meters per second
+    pub speed: Option<f64>,
+    pub timestamp: DateTime<Utc>,
+    pub source: PointSource,
+}
+
+impl TrackPoint {
+    pub fn to_point(&self, relative_timestamp: &DateTime<Utc>) -> Point {
+        Point {
+            lat: self.lat,
+            lng: self.lng,
+            altitude: self.altitude,
+            accuracy: self.accuracy,
+            estimated_error: None,
+            timestamp: self.timestamp,
+            relative_seconds: (self.timestamp - *relative_timestamp).num_seconds(),
+            source: self.source,
+        }
+    }
+}
+
+/// Give each position along `route` a time between `start_time` and
+/// `end_time` in proportion to the distance travelled to reach it, for paths
+/// that only have a start and end time
+pub fn spread_by_distance(
+    route: &[(f64, f64)],
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+) -> Vec<DateTime<Utc>> {
+    let mut travelled = vec![0.0];
+    for pair in route.windows(2) {
+        travelled.push(travelled.last().unwrap() + great_circle_distance(pair[0], pair[1]));
+    }
+    let total = *travelled.last().unwrap();
+    let duration = (end_time - start_time).num_milliseconds() as f64;
+
+    (0..route.len())
+        .map(|i| {
+            // Spread evenly if the route doesn't go anywhere
+            let progress = if total > 0.0 {
+                travelled[i] / total
+            } else {
+                i as f64 / (route.len() - 1).max(1) as f64
+            };
+            start_time + TimeDelta::milliseconds((duration * progress).round() as i64)
+        })
+        .collect()
+}
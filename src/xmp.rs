@@ -0,0 +1,431 @@
+use chrono::{DateTime, Utc};
+use quick_xml::{
+    NsReader, Writer,
+    events::{BytesStart, Event},
+    name::{LocalName, Namespace, NamespaceResolver, PrefixDeclaration, ResolveResult},
+};
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::line::Point;
+
+const EXIF_NAMESPACE: &str = "http://ns.adobe.com/exif/1.0/";
+
+/// Skeleton used when a photo has no sidecar yet; the GPS properties are
+/// merged into it exactly like into an existing sidecar
+const EMPTY_SIDECAR: &str = r#"<?xpacket begin="﻿" id="W5M0MpCehiHzreSzNTczkc9d"?>
+<x:xmpmeta xmlns:x="adobe:ns:meta/">
+ <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+  <rdf:Description rdf:about=""/>
+ </rdf:RDF>
+</x:xmpmeta>
+<?xpacket end="w"?>
+"#;
+
+/// Find the sidecar for a photo. darktable style `IMG_0001.JPG.xmp` is used
+/// unless only a Lightroom style `IMG_0001.xmp` already exists.
+pub fn sidecar_path(photo_path: &Path) -> PathBuf {
+    let mut appended = photo_path.as_os_str().to_owned();
+    appended.push(".xmp");
+    let appended = PathBuf::from(appended);
+
+    let replaced = photo_path.with_extension("xmp");
+    if !appended.exists() && replaced.exists() {
+        replaced
+    } else {
+        appended
+    }
+}
+
+/// Write the GPS position to the photo's XMP sidecar, keeping everything
+/// else an existing sidecar contains
+pub fn write_sidecar(
+    photo_path: &Path,
+    point: &Point,
+    photo_time: &DateTime<Utc>,
+) -> Result<PathBuf, Box<dyn Error>> {
+    let path = sidecar_path(photo_path);
+
+    let existing = if path.exists() {
+        fs::read_to_string(&path)?
+    } else {
+        EMPTY_SIDECAR.to_string()
+    };
+
+    let merged = merge_gps(&existing, &gps_properties(point, photo_time))?;
+    fs::write(&path, merged)?;
+
+    Ok(path)
+}
+
+/// The position already in the photo's XMP sidecar, if it has one with
+/// `exif:GPSLatitude` and `exif:GPSLongitude`
+pub fn read_sidecar_position(photo_path: &Path) -> Option<(f64, f64)> {
+    let path = sidecar_path(photo_path);
+    if !path.exists() {
+        return None;
+    }
+    read_position(&fs::read_to_string(path).ok()?).ok()?
+}
+
+/// The `exif:GPS*` properties describing a position, by local name
+fn gps_properties(point: &Point, photo_time: &DateTime<Utc>) -> Vec<(&'static str, String)> {
+    let mut properties = vec![
+        ("GPSVersionID", "2.2.0.0".to_string()),
+        ("GPSLatitude", format_coordinate(point.lat, 'N', 'S')),
+        ("GPSLongitude", format_coordinate(point.lng, 'E', 'W')),
+    ];
+
+    if let Some(altitude) = point.altitude {
+        // Rational with centimetre precision
+        let centimetres = (altitude.abs() * 100.0).round() as u64;
+        properties.push(("GPSAltitude", format!("{}/100", centimetres)));
+        properties.push((
+            "GPSAltitudeRef",
+            if altitude >= 0.0 { "0" } else { "1" }.to_string(),
+        ));
+    }
+
+    if let Some(error) = point.estimated_error {
+        let centimetres = (error * 100.0).round() as u64;
+        properties.push(("GPSHPositioningError", format!("{}/100", centimetres)));
+    }
+
+    properties.push((
+        "GPSTimeStamp",
+        photo_time.format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+    ));
+
+    properties
+}
+
+/// Format a coordinate the way XMP expects it: `DDD,MM.mmmmmmR`
+fn format_coordinate(decimal: f64, positive: char, negative: char) -> String {
+    // Round to the printed precision first so 59.9999999 minutes carries
+    // into the degrees instead of being printed as 60.000000
+    let micro_minutes = (decimal.abs() * 60_000_000.0).round() as u64;
+    let degrees = micro_minutes / 60_000_000;
+    let minutes = (micro_minutes % 60_000_000) as f64 / 1_000_000.0;
+    let reference = if decimal >= 0.0 { positive } else { negative };
+    format!("{},{:.6}{}", degrees, minutes, reference)
+}
+
+/// Parse a coordinate written as `DDD,MM.mmmmmmR` or `DDD,MM,SSR`
+fn parse_coordinate(value: &str) -> Option<f64> {
+    let value = value.trim();
+    let reference = value.chars().last()?;
+    let mut parts = value[..value.len() - reference.len_utf8()].split(',');
+
+    let degrees: f64 = parts.next()?.trim().parse().ok()?;
+    let minutes: f64 = parts.next()?.trim().parse().ok()?;
+    let seconds: f64 = match parts.next() {
+        Some(seconds) => seconds.trim().parse().ok()?,
+        None => 0.0,
+    };
+    let decimal = degrees + minutes / 60.0 + seconds / 3600.0;
+
+    match reference.to_ascii_uppercase() {
+        'N' | 'E' => Some(decimal),
+        'S' | 'W' => Some(-decimal),
+        _ => None,
+    }
+}
+
+/// The local name of a property of the EXIF namespace, whatever prefix the
+/// sidecar binds that namespace to
+fn exif_property<'n>(namespace: &ResolveResult, local_name: LocalName<'n>) -> Option<&'n str> {
+    match namespace {
+        ResolveResult::Bound(Namespace(uri)) if *uri == EXIF_NAMESPACE => {
+            Some(local_name.into_inner())
+        }
+        _ => None,
+    }
+}
+
+/// Whether a name is a `GPS*` property of the EXIF namespace
+fn is_gps_property(namespace: &ResolveResult, local_name: LocalName) -> bool {
+    exif_property(namespace, local_name).is_some_and(|name| name.starts_with("GPS"))
+}
+
+/// Read `exif:GPSLatitude` and `exif:GPSLongitude`, written as either
+/// attributes or elements
+fn read_position(xmp: &str) -> Result<Option<(f64, f64)>, Box<dyn Error>> {
+    let mut reader = NsReader::from_str(xmp);
+    let (mut lat, mut lng) = (None, None);
+    let mut property = None;
+
+    loop {
+        let (namespace, event) = reader.read_resolved_event()?;
+        let mut set = |name: &str, value: &str| match name {
+            "GPSLatitude" => lat = parse_coordinate(value),
+            "GPSLongitude" => lng = parse_coordinate(value),
+            _ => {}
+        };
+
+        match event {
+            Event::Start(ref element) | Event::Empty(ref element) => {
+                property = match event {
+                    Event::Start(_) => {
+                        exif_property(&namespace, element.local_name()).map(str::to_string)
+                    }
+                    _ => None,
+                };
+                for attribute in element.attributes() {
+                    let attribute = attribute?;
+                    let (namespace, local_name) =
+                        reader.resolver().resolve_attribute(attribute.key);
+                    if let Some(name) = exif_property(&namespace, local_name) {
+                        set(name, &attribute.value);
+                    }
+                }
+            }
+            Event::Text(text) => {
+                if let Some(name) = property.take() {
+                    set(&name, &text);
+                }
+            }
+            Event::End(_) => property = None,
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(lat.zip(lng))
+}
+
+/// Rewrite `xmp`, dropping any existing `exif:GPS*` attributes or elements
+/// and adding `properties` as attributes of the first `rdf:Description`
+fn merge_gps(xmp: &str, properties: &[(&str, String)]) -> Result<String, Box<dyn Error>> {
+    let mut reader = NsReader::from_str(xmp);
+    let mut writer = Writer::new(Vec::new());
+    let mut merged = false;
+    let mut skip_depth = 0;
+
+    loop {
+        let (namespace, event) = reader.read_resolved_event()?;
+        let is_gps = match &event {
+            Event::Start(element) | Event::Empty(element) => {
+                is_gps_property(&namespace, element.local_name())
+            }
+            _ => false,
+        };
+
+        if skip_depth > 0 {
+            match event {
+                Event::Start(_) => skip_depth += 1,
+                Event::End(_) => skip_depth -= 1,
+                Event::Eof => return Err("Unexpected end of XMP sidecar".into()),
+                _ => {}
+            }
+            continue;
+        }
+
+        match event {
+            Event::Start(_) if is_gps => {
+                skip_depth = 1;
+            }
+            Event::Empty(_) if is_gps => {}
+            Event::Start(element) if element.name().as_ref() == "rdf:Description" => {
+                let element =
+                    rewrite_description(&element, reader.resolver(), !merged, properties)?;
+                merged = true;
+                writer.write_event(Event::Start(element))?;
+            }
+            Event::Empty(element) if element.name().as_ref() == "rdf:Description" => {
+                let element =
+                    rewrite_description(&element, reader.resolver(), !merged, properties)?;
+                merged = true;
+                writer.write_event(Event::Empty(element))?;
+            }
+            Event::Eof => break,
+            event => writer.write_event(event)?,
+        }
+    }
+
+    if !merged {
+        return Err("XMP sidecar has no rdf:Description element".into());
+    }
+
+    Ok(String::from_utf8(writer.into_inner())?)
+}
+
+fn rewrite_description(
+    element: &BytesStart,
+    resolver: &NamespaceResolver,
+    add_properties: bool,
+    properties: &[(&str, String)],
+) -> Result<BytesStart<'static>, Box<dyn Error>> {
+    let mut rewritten = BytesStart::new(element.name().as_ref().to_string());
+
+    for attribute in element.attributes() {
+        let attribute = attribute?;
+        let (namespace, local_name) = resolver.resolve_attribute(attribute.key);
+        if !is_gps_property(&namespace, local_name) {
+            rewritten.push_attribute(attribute);
+        }
+    }
+
+    if add_properties {
+        // Write under the prefix the sidecar already binds the EXIF
+        // namespace to, here or on an enclosing element
+        let bound_prefix = resolver
+            .bindings()
+            .find_map(|(prefix, Namespace(uri))| match prefix {
+                PrefixDeclaration::Named(prefix) if uri == EXIF_NAMESPACE => Some(prefix),
+                _ => None,
+            });
+        let prefix = match bound_prefix {
+            Some(prefix) => prefix,
+            None => {
+                rewritten.push_attribute(("xmlns:exif", EXIF_NAMESPACE));
+                "exif"
+            }
+        };
+
+        for (name, value) in properties {
+            rewritten.push_attribute((format!("{}:{}", prefix, name).as_str(), value.as_str()));
+        }
+    }
+
+    Ok(rewritten.into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::line::PointSource;
+
+    fn point() -> Point {
+        Point {
+            lat: 50.1447774,
+            lng: -5.6015006,
+            altitude: Some(75.6),
+            accuracy: None,
+            estimated_error: Some(12.345),
+            timestamp: DateTime::parse_from_rfc3339("2023-08-29T12:37:20+01:00")
+                .unwrap()
+                .with_timezone(&Utc),
+            relative_seconds: 0,
+            source: PointSource::RawSignal,
+        }
+    }
+
+    #[test]
+    fn test_new_sidecar() {
+        let point = point();
+        let merged = merge_gps(EMPTY_SIDECAR, &gps_properties(&point, &point.timestamp)).unwrap();
+
+        assert!(merged.contains(r#"xmlns:exif="http://ns.adobe.com/exif/1.0/""#));
+        assert!(merged.contains(r#"exif:GPSLatitude="50,8.686644N""#));
+        assert!(merged.contains(r#"exif:GPSLongitude="5,36.090036W""#));
+        assert!(merged.contains(r#"exif:GPSAltitude="7560/100""#));
+        assert!(merged.contains(r#"exif:GPSAltitudeRef="0""#));
+        assert!(merged.contains(r#"exif:GPSHPositioningError="1235/100""#));
+        assert!(merged.contains(r#"exif:GPSTimeStamp="2023-08-29T11:37:20Z""#));
+    }
+
+    #[test]
+    fn test_merge_existing_sidecar() {
+        let existing = r#"<x:xmpmeta xmlns:x="adobe:ns:meta/">
+ <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+  <rdf:Description rdf:about=""
+    xmlns:exif="http://ns.adobe.com/exif/1.0/"
+    xmlns:darktable="http://darktable.sf.net/"
+    darktable:xmp_version="5"
+    exif:GPSLatitude="1,0.000000N">
+   <exif:GPSLongitude>2,0.000000E</exif:GPSLongitude>
+   <darktable:history>
+    <rdf:Seq>
+     <rdf:li darktable:operation="exposure"/>
+    </rdf:Seq>
+   </darktable:history>
+  </rdf:Description>
+ </rdf:RDF>
+</x:xmpmeta>"#;
+
+        let point = point();
+        let merged = merge_gps(existing, &gps_properties(&point, &point.timestamp)).unwrap();
+
+        assert!(merged.contains(r#"darktable:xmp_version="5""#));
+        assert!(merged.contains(r#"<rdf:li darktable:operation="exposure"/>"#));
+        assert_eq!(merged.matches("xmlns:exif=").count(), 1);
+        assert_eq!(merged.matches("GPSLatitude").count(), 1);
+        assert_eq!(merged.matches("GPSLongitude").count(), 1);
+        assert!(merged.contains(r#"exif:GPSLatitude="50,8.686644N""#));
+        assert!(!merged.contains("2,0.000000E"));
+    }
+
+    #[test]
+    fn test_merge_other_exif_prefix() {
+        let existing = r#"<x:xmpmeta xmlns:x="adobe:ns:meta/">
+ <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+  <rdf:Description rdf:about=""
+    xmlns:ns1="http://ns.adobe.com/exif/1.0/"
+    xmlns:other="http://example.com/other/"
+    ns1:GPSLatitude="1,0.000000N"
+    other:GPSLatitude="kept">
+   <ns1:GPSLongitude>2,0.000000E</ns1:GPSLongitude>
+  </rdf:Description>
+ </rdf:RDF>
+</x:xmpmeta>"#;
+
+        let point = point();
+        let merged = merge_gps(existing, &gps_properties(&point, &point.timestamp)).unwrap();
+
+        assert!(!merged.contains("2,0.000000E"));
+        assert!(merged.contains(r#"other:GPSLatitude="kept""#));
+        assert!(merged.contains(r#"ns1:GPSLatitude="50,8.686644N""#));
+        assert!(!merged.contains("xmlns:exif"));
+    }
+
+    #[test]
+    fn test_merge_namespace_on_enclosing_element() {
+        let existing = r#"<x:xmpmeta xmlns:x="adobe:ns:meta/">
+ <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#"
+   xmlns:exif="http://ns.adobe.com/exif/1.0/">
+  <rdf:Description rdf:about="" exif:GPSLatitude="1,0.000000N"/>
+ </rdf:RDF>
+</x:xmpmeta>"#;
+
+        let point = point();
+        let merged = merge_gps(existing, &gps_properties(&point, &point.timestamp)).unwrap();
+
+        assert_eq!(merged.matches("xmlns:exif=").count(), 1);
+        assert_eq!(merged.matches("exif:GPSLatitude").count(), 1);
+        assert!(merged.contains(r#"exif:GPSLatitude="50,8.686644N""#));
+    }
+
+    #[test]
+    fn test_read_position() {
+        let attributes = r#"<x:xmpmeta xmlns:x="adobe:ns:meta/">
+ <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+  <rdf:Description xmlns:exif="http://ns.adobe.com/exif/1.0/"
+    exif:GPSLatitude="50,8.686644N" exif:GPSLongitude="5,36,5.4W"/>
+ </rdf:RDF>
+</x:xmpmeta>"#;
+        let (lat, lng) = read_position(attributes).unwrap().unwrap();
+        assert!((lat - 50.1447774).abs() < 1e-6);
+        assert!((lng - -5.6015).abs() < 1e-6);
+
+        let elements = r#"<x:xmpmeta xmlns:x="adobe:ns:meta/">
+ <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+  <rdf:Description xmlns:ns1="http://ns.adobe.com/exif/1.0/">
+   <ns1:GPSLatitude>1,30.000000S</ns1:GPSLatitude>
+   <ns1:GPSLongitude>2,0.000000E</ns1:GPSLongitude>
+  </rdf:Description>
+ </rdf:RDF>
+</x:xmpmeta>"#;
+        assert_eq!(read_position(elements).unwrap(), Some((-1.5, 2.0)));
+
+        assert_eq!(read_position(EMPTY_SIDECAR).unwrap(), None);
+    }
+
+    #[test]
+    fn test_format_coordinate_carries_minutes() {
+        assert_eq!(format_coordinate(50.99999999999, 'N', 'S'), "51,0.000000N");
+        assert_eq!(format_coordinate(-5.6015006, 'E', 'W'), "5,36.090036W");
+    }
+}